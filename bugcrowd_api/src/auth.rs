@@ -0,0 +1,143 @@
+use std::fmt;
+
+use anyhow::Context;
+use log::{debug, info};
+use reqwest::Client;
+use tokio::sync::Mutex;
+use totp_rs::{Algorithm, Secret, TOTP};
+
+use crate::models::AuthSession;
+
+#[derive(Clone)]
+pub struct Credentials {
+    pub email: String,
+    pub password: String,
+    /// Base32 secret of the authenticator app, required when 2FA is enabled
+    pub totp_secret: Option<String>,
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("email", &self.email)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Credentials {
+    fn otp_code(&self) -> Result<String, anyhow::Error> {
+        let secret = self
+            .totp_secret
+            .as_ref()
+            .context("two-factor code requested but no TOTP secret configured")?;
+        let secret = Secret::Encoded(secret.replace(' ', "").to_uppercase())
+            .to_bytes()
+            .map_err(|error| anyhow::Error::msg(format!("invalid TOTP secret: {error:?}")))?;
+
+        let totp = TOTP::new_unchecked(Algorithm::SHA1, 6, 1, 30, secret);
+        Ok(totp.generate_current()?)
+    }
+}
+
+/// Signs in like the browser does: fetch the CSRF token from the sign in page,
+/// post the credentials, answer the OTP challenge if one is shown, then check
+/// that the session is authenticated. The session cookie is captured by the
+/// client's cookie store.
+///
+/// Sign ins are serialized by the lock on `credentials`, and skipped when the
+/// session is already authenticated.
+pub(crate) async fn login(
+    request_client: &Client,
+    base_url: &str,
+    credentials: &Mutex<Credentials>,
+) -> Result<(), anyhow::Error> {
+    let credentials = credentials.lock().await;
+    if is_authenticated(request_client, base_url).await? {
+        debug!("session already authenticated, skipping sign in");
+        return Ok(());
+    }
+
+    debug!("signing in as {}", credentials.email);
+
+    let sign_in_page = request_client
+        .get(format!("{base_url}/user/sign_in"))
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+    let csrf_token = find_csrf_token(&sign_in_page).context("no CSRF token on the sign in page")?;
+
+    let response = request_client
+        .post(format!("{base_url}/user/sign_in"))
+        .header("x-csrf-token", &csrf_token)
+        .form(&[
+            ("authenticity_token", csrf_token.as_str()),
+            ("user[email]", credentials.email.as_str()),
+            ("user[password]", credentials.password.as_str()),
+        ])
+        .send()
+        .await?
+        .error_for_status()?;
+
+    let otp_requested = response.url().path().contains("otp");
+    let page = response.text().await?;
+    if otp_requested || page.contains("otp_code") {
+        debug!("answering OTP challenge");
+        let csrf_token = find_csrf_token(&page).unwrap_or(csrf_token);
+        let otp_code = credentials.otp_code()?;
+
+        request_client
+            .post(format!("{base_url}/auth/otp-challenge"))
+            .header("x-csrf-token", &csrf_token)
+            .form(&[
+                ("authenticity_token", csrf_token.as_str()),
+                ("otp_code", otp_code.as_str()),
+            ])
+            .send()
+            .await?
+            .error_for_status()?;
+    }
+
+    if !is_authenticated(request_client, base_url).await? {
+        anyhow::bail!("sign in failed, session is not authenticated");
+    }
+
+    info!("signed in as {}", credentials.email);
+    Ok(())
+}
+
+async fn is_authenticated(request_client: &Client, base_url: &str) -> Result<bool, anyhow::Error> {
+    let session = request_client
+        .get(format!("{base_url}/auth/session"))
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<AuthSession>()
+        .await?;
+    Ok(session.user.is_some())
+}
+
+/// Value of `<meta name="csrf-token" content="...">`
+fn find_csrf_token(page: &str) -> Option<String> {
+    let meta = page.find("name=\"csrf-token\"")?;
+    let tag_start = page[..meta].rfind('<')?;
+    let tag_end = meta + page[meta..].find('>')?;
+    let tag = &page[tag_start..tag_end];
+
+    let content = tag.find("content=\"")? + "content=\"".len();
+    let content_end = content + tag[content..].find('"')?;
+    Some(tag[content..content_end].to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csrf_meta() {
+        let page = r#"<head><meta content="abc+/=" name="csrf-token" /></head>"#;
+        assert_eq!(find_csrf_token(page).as_deref(), Some("abc+/="));
+        assert_eq!(find_csrf_token("<head></head>"), None);
+    }
+}
@@ -0,0 +1,125 @@
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::{Arc, Mutex},
+};
+
+use reqwest::{
+    Response, StatusCode, Url,
+    header::{ETAG, HeaderMap, HeaderValue, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+};
+
+/// Responses kept at most, the least recently used one is evicted first
+const MAX_ENTRIES: usize = 1000;
+
+/// Identifies a response body. Pollers keep the revision they last processed
+/// and skip their diff when the response didn't change since.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Revision(u64);
+
+impl Revision {
+    fn of(body: &[u8]) -> Self {
+        let mut hasher = DefaultHasher::new();
+        body.hash(&mut hasher);
+        Self(hasher.finish())
+    }
+}
+
+/// Result of a request made on behalf of a poller
+#[derive(Debug)]
+pub enum Polled<T> {
+    /// Same revision as the one the poller already processed
+    Unchanged,
+    Changed(T, Revision),
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct CachedBody {
+    pub body: Arc<[u8]>,
+    pub revision: Revision,
+}
+
+#[derive(Debug)]
+struct Entry {
+    etag: Option<HeaderValue>,
+    last_modified: Option<HeaderValue>,
+    body: Arc<[u8]>,
+    revision: Revision,
+    used_at: u64,
+}
+
+/// Last body of every URL that sent an `ETag` or `Last-Modified` validator,
+/// shared by every clone of the client.
+#[derive(Debug, Default)]
+pub(crate) struct ResponseCache {
+    entries: Mutex<(u64, HashMap<Url, Entry>)>,
+}
+
+impl ResponseCache {
+    /// Conditional headers for a request to `url`
+    pub fn validators(&self, url: &Url) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        let entries = self.entries.lock().unwrap();
+        if let Some(entry) = entries.1.get(url) {
+            if let Some(etag) = &entry.etag {
+                headers.insert(IF_NONE_MATCH, etag.clone());
+            }
+            if let Some(last_modified) = &entry.last_modified {
+                headers.insert(IF_MODIFIED_SINCE, last_modified.clone());
+            }
+        }
+        headers
+    }
+
+    /// Reads the body of a successful response, or the cached one on a 304
+    pub async fn body(&self, url: &Url, response: Response) -> Result<CachedBody, anyhow::Error> {
+        if response.status() == StatusCode::NOT_MODIFIED {
+            let mut entries = self.entries.lock().unwrap();
+            let (clock, entries) = &mut *entries;
+            let entry = entries
+                .get_mut(url)
+                .ok_or_else(|| anyhow::Error::msg(format!("304 for uncached url {url}")))?;
+
+            *clock += 1;
+            entry.used_at = *clock;
+            return Ok(CachedBody {
+                body: entry.body.clone(),
+                revision: entry.revision,
+            });
+        }
+
+        let response = response.error_for_status()?;
+        let etag = response.headers().get(ETAG).cloned();
+        let last_modified = response.headers().get(LAST_MODIFIED).cloned();
+        let body: Arc<[u8]> = response.bytes().await?.to_vec().into();
+        let revision = Revision::of(&body);
+
+        let mut entries = self.entries.lock().unwrap();
+        let (clock, entries) = &mut *entries;
+        if etag.is_none() && last_modified.is_none() {
+            entries.remove(url);
+        } else {
+            if entries.len() >= MAX_ENTRIES && !entries.contains_key(url) {
+                let oldest = entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.used_at)
+                    .map(|(url, _)| url.clone());
+                if let Some(oldest) = oldest {
+                    entries.remove(&oldest);
+                }
+            }
+
+            *clock += 1;
+            let entry = Entry {
+                etag,
+                last_modified,
+                body: body.clone(),
+                revision,
+                used_at: *clock,
+            };
+            entries.insert(url.clone(), entry);
+        }
+
+        Ok(CachedBody { body, revision })
+    }
+}
@@ -1,20 +1,79 @@
-use std::time::Duration;
+use std::sync::Arc;
 
-use log::{debug, error};
+use futures::{Stream, TryStreamExt, future};
 use reqwest::{
-    Client, ClientBuilder, Method,
+    Client, ClientBuilder, IntoUrl, Response, StatusCode, Url,
     header::{HeaderMap, HeaderValue},
 };
+use serde::de::DeserializeOwned;
+use tokio::sync::{Mutex, watch};
 
-use crate::models::{CrowdStream, DisclosedReport, HallOfFame, Hero};
+use crate::models::{
+    ANONYMOUS_HERO_NAME, Announcement, Announcements, CrowdStream, CrowdStreamActivities,
+    DisclosedReport, DisclosureCursor, Engagement, EngagementBrief, EngagementListing, HallOfFame,
+    Hero, ResearcherProfile,
+};
+use crate::{
+    auth::{self, Credentials},
+    cache::{CachedBody, Polled, ResponseCache, Revision},
+    paginate::{Page, paginate},
+    rate_limit::RateLimiter,
+    session::{SessionCookies, SessionKeepalive, SessionState},
+};
+
+pub const BUGCROWD_URL: &str = "https://bugcrowd.com";
+/// 25 heros per page
+const MAX_HALL_OF_FAME_PAGES: u64 = 400;
 
 #[derive(Debug, Clone)]
 pub struct BugcrowdApi {
     request_client: Client,
+    base_url: Arc<str>,
+    cookies: Arc<SessionCookies>,
+    credentials: Option<Arc<Mutex<Credentials>>>,
+    session: Arc<SessionKeepalive>,
+    rate_limiter: Arc<RateLimiter>,
+    cache: Arc<ResponseCache>,
 }
 
-impl BugcrowdApi {
-    pub fn new(session_token: Option<String>) -> Self {
+#[derive(Debug, Default)]
+pub struct BugcrowdApiBuilder {
+    session_token: Option<String>,
+    credentials: Option<Credentials>,
+    base_url: Option<String>,
+    rate_limiter: Option<RateLimiter>,
+}
+
+impl BugcrowdApiBuilder {
+    pub fn session_token(mut self, session_token: Option<String>) -> Self {
+        self.session_token = session_token;
+        self
+    }
+
+    /// Used to sign in when there is no session, or it expired
+    pub fn credentials(mut self, credentials: Option<Credentials>) -> Self {
+        self.credentials = credentials;
+        self
+    }
+
+    /// Defaults to [`BUGCROWD_URL`]
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    /// Defaults to [`DEFAULT_REQUESTS_PER_SECOND`](crate::rate_limit::DEFAULT_REQUESTS_PER_SECOND)
+    /// and [`DEFAULT_MAX_CONCURRENCY`](crate::rate_limit::DEFAULT_MAX_CONCURRENCY)
+    pub fn rate_limit(
+        mut self,
+        requests_per_second: f64,
+        max_concurrency: usize,
+    ) -> Result<Self, anyhow::Error> {
+        self.rate_limiter = Some(RateLimiter::new(requests_per_second, max_concurrency)?);
+        Ok(self)
+    }
+
+    pub fn build(self) -> BugcrowdApi {
         let mut default_headers = HeaderMap::new();
         default_headers.insert(
             "user-agent",
@@ -22,68 +81,246 @@ impl BugcrowdApi {
                 "BugcrowdTracker/1.0 (+https://github.com/hackermondev/bugcrowd-tracker)",
             ),
         );
-        if let Some(session_token) = session_token {
-            default_headers.insert(
-                "Cookie",
-                HeaderValue::from_str(&format!("_bugcrowd_session={session_token}")).unwrap(),
-            );
-        }
 
+        let base_url: Arc<str> = self
+            .base_url
+            .as_deref()
+            .unwrap_or(BUGCROWD_URL)
+            .trim_end_matches('/')
+            .into();
+        let cookies = Arc::new(SessionCookies::new(self.session_token, &base_url));
         let client = ClientBuilder::new()
             .default_headers(default_headers)
+            .cookie_provider(cookies.clone())
             .http2_prior_knowledge()
             .build()
             .unwrap();
 
-        {
-            let client = client.clone();
-            tokio::task::spawn(async move { __keepalive_session_background(client).await });
-        }
+        let credentials = self
+            .credentials
+            .map(|credentials| Arc::new(Mutex::new(credentials)));
+        let session =
+            SessionKeepalive::spawn(client.clone(), base_url.clone(), credentials.clone());
 
-        Self {
+        BugcrowdApi {
             request_client: client,
+            base_url,
+            cookies,
+            credentials,
+            session: Arc::new(session),
+            rate_limiter: Arc::new(self.rate_limiter.unwrap_or_default()),
+            cache: Arc::default(),
         }
     }
+}
+
+impl BugcrowdApi {
+    pub fn new(session_token: Option<String>) -> Self {
+        Self::builder().session_token(session_token).build()
+    }
+
+    pub fn builder() -> BugcrowdApiBuilder {
+        BugcrowdApiBuilder::default()
+    }
+
+    /// Signs in with the configured credentials, replacing the session token
+    pub async fn login(&self) -> Result<(), anyhow::Error> {
+        let credentials = self
+            .credentials
+            .as_ref()
+            .ok_or_else(|| anyhow::Error::msg("no credentials configured"))?;
+        auth::login(&self.request_client, &self.base_url, credentials).await
+    }
+
+    /// Replaces the session token used by every clone of the client
+    pub fn set_session_token(&self, session_token: String) {
+        self.cookies.set_session_token(session_token);
+    }
+
+    /// Current session token, including refreshes sent back by Bugcrowd
+    pub fn session_token(&self) -> watch::Receiver<Option<String>> {
+        self.cookies.token()
+    }
+
+    /// Authentication state of the session, updated by the keepalive task.
+    /// The task stops once every clone of the client is dropped.
+    pub fn session_state(&self) -> watch::Receiver<SessionState> {
+        self.session.state()
+    }
+
+    /// Rate limiter shared by every clone of the client
+    pub fn rate_limiter(&self) -> &RateLimiter {
+        &self.rate_limiter
+    }
+
+    /// Rate limited GET, retried a few times when Bugcrowd answers with a 429
+    async fn get(&self, url: impl IntoUrl) -> Result<Response, reqwest::Error> {
+        self.get_with_headers(url.into_url()?, HeaderMap::new())
+            .await
+    }
+
+    async fn get_with_headers(
+        &self,
+        url: Url,
+        headers: HeaderMap,
+    ) -> Result<Response, reqwest::Error> {
+        const MAX_ATTEMPTS: usize = 3;
+
+        let mut attempt = 1;
+        loop {
+            let permit = self.rate_limiter.acquire().await;
+            let request = self
+                .request_client
+                .get(url.clone())
+                .headers(headers.clone());
+            let response = request.send().await?;
+            drop(permit);
+
+            self.rate_limiter.observe(&response);
+            if response.status() != StatusCode::TOO_MANY_REQUESTS || attempt == MAX_ATTEMPTS {
+                return Ok(response);
+            }
+            attempt += 1;
+        }
+    }
+
+    /// Conditional GET, served from the response cache when Bugcrowd answers
+    /// with a 304
+    async fn get_cached(&self, url: impl IntoUrl) -> Result<CachedBody, anyhow::Error> {
+        let url = url.into_url()?;
+        let validators = self.cache.validators(&url);
+        let response = self.get_with_headers(url.clone(), validators).await?;
+        self.cache.body(&url, response).await
+    }
+
+    async fn get_json<T: DeserializeOwned>(&self, url: impl IntoUrl) -> Result<T, anyhow::Error> {
+        let cached = self.get_cached(url).await?;
+        Ok(serde_json::from_slice(&cached.body)?)
+    }
+
+    /// Engagements visible to the session, public ones and private invites
+    pub async fn engagements(&self) -> Result<Vec<Engagement>, anyhow::Error> {
+        const MAX_PAGES: u64 = 50;
+        self.engagements_stream(MAX_PAGES).try_collect().await
+    }
+
+    /// Streaming version of [`engagements`](Self::engagements)
+    pub fn engagements_stream(
+        &self,
+        max_pages: u64,
+    ) -> impl Stream<Item = Result<Engagement, anyhow::Error>> + '_ {
+        let key = |engagement: &Engagement| Some(engagement.brief_url.clone());
+        paginate(
+            "engagements".into(),
+            max_pages,
+            key,
+            move |page| async move {
+                let url = format!("{}/engagements.json?page={page}", self.base_url);
+                let listing = self.get_json::<EngagementListing>(url).await?;
+                Ok(Page {
+                    items: listing.engagements,
+                    total_count: listing.pagination_meta.total_count,
+                })
+            },
+        )
+    }
 
     pub async fn has_program_access(&self, program: &str) -> Result<bool, anyhow::Error> {
-        let url = format!("https://bugcrowd.com/engagements/{program}/announcements.json");
-        let response = self.request_client.get(url).send().await?;
+        let url = format!("{}/engagements/{program}/announcements.json", self.base_url);
+        let response = self.get(url).await?;
         Ok(response.status().is_success())
     }
 
-    pub async fn hall_of_fame(&self, program: &str) -> Result<Vec<Hero>, anyhow::Error> {
-        let mut heros = vec![];
-        let mut page = 1;
+    /// Program announcements, newest first
+    pub async fn announcements(&self, program: &str) -> Result<Vec<Announcement>, anyhow::Error> {
+        let url = format!("{}/engagements/{program}/announcements.json", self.base_url);
+        let announcements = self.get_json::<Announcements>(url).await?;
+        Ok(announcements.announcements)
+    }
 
-        loop {
-            let url = format!(
-                "https://bugcrowd.com/engagements/{program}/hall_of_fames.json?page={page}"
-            );
-            let response = self.request_client.get(url).send().await?;
-            let response = response.error_for_status()?;
+    /// Scope, reward table and status of the engagement
+    pub async fn engagement_brief(&self, program: &str) -> Result<EngagementBrief, anyhow::Error> {
+        let url = format!("{}/engagements/{program}/brief.json", self.base_url);
+        let brief = self.get_json::<EngagementBrief>(url).await?;
+        Ok(brief)
+    }
 
-            let mut hof = response.json::<HallOfFame>().await?;
-            heros.append(&mut hof.presented_heroes.heroes);
-            page += 1;
+    /// Public profile of a researcher (`/h/{username}`)
+    pub async fn researcher(&self, username: &str) -> Result<ResearcherProfile, anyhow::Error> {
+        let url = format!("{}/h/{username}/profile.json", self.base_url);
+        let profile = self.get_json::<ResearcherProfile>(url).await?;
+        Ok(profile)
+    }
 
-            if (hof.pagination_meta.total_count == heros.len() as u64)
-                || hof.presented_heroes.total == 0
-            {
-                break;
-            }
+    pub async fn hall_of_fame(&self, program: &str) -> Result<Vec<Hero>, anyhow::Error> {
+        let stream = self.hall_of_fame_stream(program, MAX_HALL_OF_FAME_PAGES);
+        ranked_heros(stream).await
+    }
+
+    /// Like [`hall_of_fame`](Self::hall_of_fame), but only checks the first
+    /// page when it is the same as at `since`. Changes limited to later pages
+    /// are picked up once page 1 changes too.
+    pub async fn hall_of_fame_since(
+        &self,
+        program: &str,
+        since: Option<Revision>,
+    ) -> Result<Polled<Vec<Hero>>, anyhow::Error> {
+        let first_page = self.get_cached(hall_of_fame_url(&self.base_url, program, 1));
+        let first_page = first_page.await?;
+        if since == Some(first_page.revision) {
+            return Ok(Polled::Unchanged);
         }
 
-        heros.sort_by_key(|h| h.points);
-        heros.reverse();
-        let heros = heros
-            .into_iter()
-            .enumerate()
-            .map(|(rank, hero)| Hero {
-                rank: rank + 1,
-                ..hero
-            })
-            .collect();
-        Ok(heros)
+        // Page 1 was just fetched, only the following pages are requested
+        let hof = serde_json::from_slice::<HallOfFame>(&first_page.body)?;
+        let stream = self.hall_of_fame_pages(program, MAX_HALL_OF_FAME_PAGES, Some(hof));
+        let heros = ranked_heros(stream).await?;
+        Ok(Polled::Changed(heros, first_page.revision))
+    }
+
+    /// Heros in the order returned by Bugcrowd, pages are fetched as the
+    /// stream is polled. `rank` is only assigned by [`hall_of_fame`](Self::hall_of_fame).
+    pub fn hall_of_fame_stream<'a>(
+        &'a self,
+        program: &'a str,
+        max_pages: u64,
+    ) -> impl Stream<Item = Result<Hero, anyhow::Error>> + 'a {
+        self.hall_of_fame_pages(program, max_pages, None)
+    }
+
+    /// Hall of fame stream starting with `first_page` when it was already fetched
+    fn hall_of_fame_pages<'a>(
+        &'a self,
+        program: &'a str,
+        max_pages: u64,
+        mut first_page: Option<HallOfFame>,
+    ) -> impl Stream<Item = Result<Hero, anyhow::Error>> + 'a {
+        let key =
+            |hero: &Hero| (hero.username != ANONYMOUS_HERO_NAME).then(|| hero.username.clone());
+        let name = format!("hall of fame of {program}");
+        paginate(name, max_pages, key, move |page| {
+            let fetched = first_page.take().filter(|_| page == 1);
+            async move {
+                let hof = match fetched {
+                    Some(hof) => hof,
+                    None => {
+                        let url = hall_of_fame_url(&self.base_url, program, page);
+                        self.get_json::<HallOfFame>(url).await?
+                    }
+                };
+
+                let hide_points = hof.presented_heroes.hide_points;
+                let mut heros = hof.presented_heroes.heroes;
+                for hero in &mut heros {
+                    hero.points_hidden = hide_points;
+                }
+
+                Ok(Page {
+                    items: heros,
+                    total_count: hof.pagination_meta.total_count,
+                })
+            }
+        })
     }
 
     pub async fn last_disclosed_report(
@@ -91,12 +328,10 @@ impl BugcrowdApi {
         program: &str,
     ) -> Result<Option<DisclosedReport>, anyhow::Error> {
         let url = format!(
-            "https://bugcrowd.com/engagements/{program}/crowdstream.json?page=1&filter_by=disclosures"
+            "{}/engagements/{program}/crowdstream.json?page=1&filter_by=disclosures",
+            self.base_url
         );
-        let response = self.request_client.get(url).send().await?;
-        let response = response.error_for_status()?;
-
-        let crowdstream = response.json::<CrowdStream>().await?;
+        let crowdstream = self.get_json::<CrowdStream>(url).await?;
         let mut reports = crowdstream.results;
         Ok(if !reports.is_empty() {
             Some(reports.remove(0))
@@ -105,58 +340,133 @@ impl BugcrowdApi {
         })
     }
 
+    /// Single page of the unfiltered Crowdstream (accepted, rewarded and
+    /// disclosed submissions), newest first.
+    pub async fn crowdstream_activities(
+        &self,
+        program: &str,
+        page: u64,
+    ) -> Result<CrowdStreamActivities, anyhow::Error> {
+        let url = format!(
+            "{}/engagements/{program}/crowdstream.json?page={page}",
+            self.base_url
+        );
+        let activities = self.get_json::<CrowdStreamActivities>(url).await?;
+        Ok(activities)
+    }
+
+    /// Reports disclosed after `cursor`, newest first. Stops at the first
+    /// report older than the cursor or after `max_pages` pages.
     pub async fn disclosed_reports_after(
         &self,
         program: &str,
-        id: &str,
+        cursor: &DisclosureCursor,
+        max_pages: u64,
     ) -> Result<Vec<DisclosedReport>, anyhow::Error> {
-        let mut reports = vec![];
-        let mut page = 1;
+        self.disclosed_reports_stream(program, max_pages)
+            .try_take_while(|r| future::ready(Ok(r.disclosed_at >= cursor.disclosed_at)))
+            .try_filter(|r| future::ready(cursor.is_new(r)))
+            .try_collect()
+            .await
+    }
 
-        loop {
+    /// Disclosed reports, newest first, pages are fetched as the stream is polled
+    pub fn disclosed_reports_stream<'a>(
+        &'a self,
+        program: &'a str,
+        max_pages: u64,
+    ) -> impl Stream<Item = Result<DisclosedReport, anyhow::Error>> + 'a {
+        let key = |report: &DisclosedReport| Some(report.id.clone());
+        let name = format!("disclosed reports of {program}");
+        paginate(name, max_pages, key, move |page| async move {
             let url = format!(
-                "https://bugcrowd.com/engagements/{program}/crowdstream.json?page={page}&filter_by=disclosures"
+                "{}/engagements/{program}/crowdstream.json?page={page}&filter_by=disclosures",
+                self.base_url
             );
-            let response = self.request_client.get(url).send().await?;
-            let response = response.error_for_status()?;
+            let crowdstream = self.get_json::<CrowdStream>(url).await?;
+            Ok(Page {
+                items: crowdstream.results,
+                total_count: crowdstream.pagination_meta.total_count,
+            })
+        })
+    }
+}
 
-            let crowdstream = response.json::<CrowdStream>().await?;
-            let range_end = crowdstream.results.iter().position(|r| r.id == id);
-            let found_initial_report = range_end.is_some();
+fn hall_of_fame_url(base_url: &str, program: &str, page: u64) -> String {
+    format!("{base_url}/engagements/{program}/hall_of_fames.json?page={page}")
+}
 
-            let range_end = range_end.unwrap_or(crowdstream.results.len());
-            reports.append(&mut (crowdstream.results[..range_end]).to_vec());
-            page += 1;
+async fn ranked_heros(
+    stream: impl Stream<Item = Result<Hero, anyhow::Error>>,
+) -> Result<Vec<Hero>, anyhow::Error> {
+    let mut heros: Vec<Hero> = stream.try_collect().await?;
+    let hide_points = heros.iter().any(|h| h.points_hidden);
+    rank_heros(&mut heros, hide_points);
+    Ok(heros)
+}
 
-            if crowdstream.pagination_meta.total_count == reports.len() as u64
-                || found_initial_report
-            {
-                break;
-            }
+/// Assigns standard competition ranks ("1, 2, 2, 4"), tied heros are ordered
+/// by username so the order doesn't flip between polls. When points are hidden
+/// the order returned by Bugcrowd is kept and ranks are positions.
+fn rank_heros(heros: &mut [Hero], hide_points: bool) {
+    if hide_points {
+        for (position, hero) in heros.iter_mut().enumerate() {
+            hero.rank = position + 1;
+            hero.points_hidden = true;
         }
+        return;
+    }
 
-        Ok(reports)
+    heros.sort_by(|a, b| {
+        b.points
+            .cmp(&a.points)
+            .then_with(|| a.username.to_lowercase().cmp(&b.username.to_lowercase()))
+    });
+
+    let mut rank = 0;
+    let mut previous_points = None;
+    for (position, hero) in heros.iter_mut().enumerate() {
+        if previous_points != Some(hero.points) {
+            rank = position + 1;
+            previous_points = Some(hero.points);
+        }
+        hero.rank = rank;
     }
 }
 
-async fn __keepalive_session_background(request_client: Client) {
-    const PING_SESSION_INTERVAL: Duration = Duration::from_secs(60);
-    let request = request_client
-        .request(
-            Method::GET,
-            "https://bugcrowd.com/auth/session?update_activity=true",
-        )
-        .build()
-        .unwrap();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    loop {
-        let request = request.try_clone().unwrap();
-        if let Err(session_ping_error) = request_client.execute(request).await {
-            error!("failed to keep session alive: {session_ping_error:#?}");
-        } else {
-            debug!("pinged session")
+    fn hero(username: &str, points: u64) -> Hero {
+        Hero {
+            username: username.to_string(),
+            points,
+            ..Default::default()
         }
+    }
 
-        tokio::time::sleep(PING_SESSION_INTERVAL).await;
+    #[test]
+    fn competition_ranking() {
+        let mut heros = vec![hero("d", 5), hero("c", 20), hero("a", 40), hero("b", 20)];
+        rank_heros(&mut heros, false);
+
+        let ranks: Vec<(&str, usize)> = heros
+            .iter()
+            .map(|h| (h.username.as_str(), h.rank))
+            .collect();
+        assert_eq!(ranks, [("a", 1), ("b", 2), ("c", 2), ("d", 4)]);
+    }
+
+    #[test]
+    fn hidden_points_keep_order() {
+        let mut heros = vec![hero("z", 0), hero("a", 0), hero("m", 0)];
+        rank_heros(&mut heros, true);
+
+        let ranks: Vec<(&str, usize)> = heros
+            .iter()
+            .map(|h| (h.username.as_str(), h.rank))
+            .collect();
+        assert_eq!(ranks, [("z", 1), ("a", 2), ("m", 3)]);
     }
 }
@@ -1,2 +1,8 @@
+pub mod auth;
+pub mod cache;
 pub mod client;
 pub mod models;
+pub mod money;
+pub(crate) mod paginate;
+pub mod rate_limit;
+pub mod session;
@@ -1,12 +1,21 @@
+use std::{fmt, str::FromStr};
+
 use chrono::{DateTime, Utc};
 use dateparser::DateTimeUtc;
-use serde::{Deserialize, Deserializer, Serialize};
+use log::warn;
+use serde::{Deserialize, Deserializer, Serialize, de::DeserializeOwned, de::Error};
+use serde_json::{Map, Value};
+
+use crate::money::Amount;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct PaginationMeta {
     pub limit: u64,
     #[serde(rename = "totalCount")]
     pub total_count: u64,
+    /// Fields not modeled above
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 // ==== Hall Of Fame ====
@@ -25,7 +34,7 @@ pub struct HallOfFameHeros {
 
 pub static ANONYMOUS_HERO_NAME: &str = "Private user";
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
 pub struct Hero {
     #[serde(rename = "accentColor")]
     pub accent_color: Option<String>,
@@ -37,6 +46,13 @@ pub struct Hero {
     pub username: String,
     #[serde(default)]
     pub rank: usize,
+    /// The program hides points, `points` is always zero and `rank` is the
+    /// position on the leaderboard
+    #[serde(default)]
+    pub points_hidden: bool,
+    /// Fields not modeled above, e.g. `isCurrentUser`
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 fn deserialize_null_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
@@ -48,10 +64,138 @@ where
     Ok(opt.unwrap_or_default())
 }
 
+// ==== Priority ====
+/// Bugcrowd VRT priority, P1 being the most severe
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Priority {
+    P1,
+    P2,
+    P3,
+    P4,
+    P5,
+    /// Missing or outside P1-P5
+    #[default]
+    Unknown,
+}
+
+impl Priority {
+    /// Priorities that are awarded points, most severe first
+    pub const REWARDED: [Priority; 4] = [Priority::P1, Priority::P2, Priority::P3, Priority::P4];
+
+    pub fn from_number(number: u32) -> Self {
+        match number {
+            1 => Priority::P1,
+            2 => Priority::P2,
+            3 => Priority::P3,
+            4 => Priority::P4,
+            5 => Priority::P5,
+            _ => Priority::Unknown,
+        }
+    }
+
+    pub fn number(self) -> Option<u32> {
+        match self {
+            Priority::P1 => Some(1),
+            Priority::P2 => Some(2),
+            Priority::P3 => Some(3),
+            Priority::P4 => Some(4),
+            Priority::P5 => Some(5),
+            Priority::Unknown => None,
+        }
+    }
+
+    /// VRT technical severity
+    pub fn name(self) -> &'static str {
+        match self {
+            Priority::P1 => "Critical",
+            Priority::P2 => "High",
+            Priority::P3 => "Medium",
+            Priority::P4 => "Low",
+            Priority::P5 => "Informational",
+            Priority::Unknown => "Unknown",
+        }
+    }
+
+    /// Name and priority, e.g. "High (P2)"
+    pub fn severity(self) -> String {
+        format!("{} ({self})", self.name())
+    }
+
+    /// Embed color
+    pub fn color(self) -> u32 {
+        match self {
+            Priority::P1 => 12986408,
+            Priority::P2 => 15690752,
+            Priority::P3 => 16361509,
+            Priority::P4 => 3046706,
+            Priority::P5 => 1402304,
+            Priority::Unknown => 10395294,
+        }
+    }
+
+    /// Hall of fame points awarded for an accepted submission
+    pub fn points(self) -> i32 {
+        match self {
+            Priority::P1 => 40,
+            Priority::P2 => 20,
+            Priority::P3 => 10,
+            Priority::P4 => 5,
+            Priority::P5 | Priority::Unknown => 0,
+        }
+    }
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.number() {
+            Some(number) => write!(f, "P{number}"),
+            None => f.write_str("P?"),
+        }
+    }
+}
+
+impl FromStr for Priority {
+    type Err = String;
+
+    /// "P1" or "1"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let number = s.trim().trim_start_matches(['P', 'p']);
+        match number.parse::<u32>().map(Priority::from_number) {
+            Ok(Priority::Unknown) | Err(_) => Err(format!("unknown priority {s}")),
+            Ok(priority) => Ok(priority),
+        }
+    }
+}
+
+impl Serialize for Priority {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.number().serialize(serializer)
+    }
+}
+
+/// Accepts a number, a "P1" string or null
+impl<'de> Deserialize<'de> for Priority {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Number(u32),
+            Text(String),
+        }
+
+        Ok(match Option::<Raw>::deserialize(deserializer)? {
+            Some(Raw::Number(number)) => Priority::from_number(number),
+            Some(Raw::Text(text)) => text.parse().unwrap_or_default(),
+            None => Priority::Unknown,
+        })
+    }
+}
+
 // ==== Crowdstream ====
 #[derive(Serialize, Deserialize, Debug)]
 pub struct CrowdStream {
     pub pagination_meta: PaginationMeta,
+    #[serde(deserialize_with = "skip_invalid")]
     pub results: Vec<DisclosedReport>,
 }
 
@@ -61,13 +205,69 @@ pub struct DisclosedReport {
     pub title: String,
     pub target: Option<String>,
     pub researcher_username: Option<String>,
-    pub amount: Option<String>,
-    pub priority: u32,
-    #[serde(deserialize_with = "deserialize_common_date_formats")]
-    pub created_at: DateTime<Utc>,
+    pub amount: Option<Amount>,
+    #[serde(default)]
+    pub priority: Priority,
+    /// `None` when missing or unparseable, it is only informative
+    #[serde(default, deserialize_with = "deserialize_lenient_date")]
+    pub created_at: Option<DateTime<Utc>>,
     #[serde(deserialize_with = "deserialize_common_date_formats")]
     pub disclosed_at: DateTime<Utc>,
     pub disclosure_report_url: String,
+    /// Fields not modeled above, e.g. `substate` or `engagement_name`
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Position in the disclosed reports stream. Several reports can share a
+/// timestamp, so the ids seen most recently are kept alongside it.
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct DisclosureCursor {
+    pub disclosed_at: DateTime<Utc>,
+    pub seen_ids: Vec<String>,
+}
+
+impl DisclosureCursor {
+    /// Number of recently seen ids remembered
+    const MAX_SEEN_IDS: usize = 50;
+
+    pub fn is_new(&self, report: &DisclosedReport) -> bool {
+        report.disclosed_at >= self.disclosed_at && !self.seen_ids.contains(&report.id)
+    }
+
+    /// Moves the cursor past `reports`, newest first as they're listed
+    pub fn advance(&mut self, reports: &[DisclosedReport]) {
+        // Oldest first so the newest ids end up in front and survive truncation
+        for report in reports.iter().rev() {
+            self.disclosed_at = self.disclosed_at.max(report.disclosed_at);
+            self.seen_ids.insert(0, report.id.clone());
+        }
+        self.seen_ids.truncate(Self::MAX_SEEN_IDS);
+    }
+}
+
+/// Entries that fail to deserialize, e.g. with an unparseable date, are
+/// logged and left out instead of failing the whole page
+fn skip_invalid<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned,
+{
+    let values = Vec::<Value>::deserialize(deserializer)?;
+    let entries = values
+        .into_iter()
+        .filter_map(|value| {
+            let id = value.get("id").cloned().unwrap_or_default();
+            match serde_json::from_value(value) {
+                Ok(entry) => Some(entry),
+                Err(error) => {
+                    warn!("skipping invalid Crowdstream entry {id}: {error}");
+                    None
+                }
+            }
+        })
+        .collect();
+    Ok(entries)
 }
 
 fn deserialize_common_date_formats<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
@@ -75,6 +275,319 @@ where
     D: Deserializer<'de>,
 {
     let datetime = String::deserialize(deserializer)?;
-    let parsed = datetime.parse::<DateTimeUtc>().unwrap();
+    let parsed = datetime
+        .parse::<DateTimeUtc>()
+        .map_err(|error| D::Error::custom(format!("invalid date {datetime:?}: {error}")))?;
     Ok(parsed.0)
 }
+
+/// Like [`deserialize_optional_date`], but unparseable dates are `None`
+fn deserialize_lenient_date<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let datetime = Option::<String>::deserialize(deserializer)?;
+    Ok(
+        datetime.and_then(|datetime| match datetime.parse::<DateTimeUtc>() {
+            Ok(parsed) => Some(parsed.0),
+            Err(error) => {
+                warn!("ignoring invalid date {datetime:?}: {error}");
+                None
+            }
+        }),
+    )
+}
+
+fn deserialize_optional_date<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let datetime = Option::<String>::deserialize(deserializer)?;
+    datetime
+        .map(|datetime| datetime.parse::<DateTimeUtc>().map(|parsed| parsed.0))
+        .transpose()
+        .map_err(D::Error::custom)
+}
+
+// ==== Crowdstream Activity ====
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CrowdStreamActivities {
+    pub pagination_meta: PaginationMeta,
+    #[serde(deserialize_with = "skip_invalid")]
+    pub results: Vec<CrowdStreamActivity>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivityKind {
+    Accepted,
+    Rewarded,
+    Disclosed,
+}
+
+impl fmt::Display for ActivityKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ActivityKind::Accepted => "accepted",
+            ActivityKind::Rewarded => "rewarded",
+            ActivityKind::Disclosed => "disclosed",
+        })
+    }
+}
+
+impl FromStr for ActivityKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "accepted" => Ok(ActivityKind::Accepted),
+            "rewarded" => Ok(ActivityKind::Rewarded),
+            "disclosed" => Ok(ActivityKind::Disclosed),
+            other => Err(format!(
+                "unknown activity '{other}', expected accepted, rewarded or disclosed"
+            )),
+        }
+    }
+}
+
+/// Any entry of the Crowdstream, disclosed or not. Undisclosed submissions
+/// usually have no title.
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct CrowdStreamActivity {
+    pub id: String,
+    pub title: Option<String>,
+    pub target: Option<String>,
+    pub researcher_username: Option<String>,
+    pub amount: Option<Amount>,
+    #[serde(default)]
+    pub priority: Priority,
+    #[serde(default, deserialize_with = "deserialize_optional_date")]
+    pub accepted_at: Option<DateTime<Utc>>,
+    #[serde(default, deserialize_with = "deserialize_optional_date")]
+    pub disclosed_at: Option<DateTime<Utc>>,
+    pub disclosure_report_url: Option<String>,
+}
+
+impl CrowdStreamActivity {
+    pub fn kind(&self) -> ActivityKind {
+        if self.disclosure_report_url.is_some() || self.disclosed_at.is_some() {
+            ActivityKind::Disclosed
+        } else if self
+            .amount
+            .as_ref()
+            .is_some_and(|amount| !amount.raw.is_empty())
+        {
+            ActivityKind::Rewarded
+        } else {
+            ActivityKind::Accepted
+        }
+    }
+}
+
+// ==== Engagement Brief ====
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct EngagementBrief {
+    /// Program status, such as "live", "paused" or "ended"
+    pub status: String,
+    #[serde(default)]
+    pub targets: Vec<Target>,
+    #[serde(default)]
+    pub rewards: Vec<RewardRange>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Target {
+    pub name: String,
+    pub in_scope: bool,
+    pub category: Option<String>,
+}
+
+/// Reward range in dollars for a priority
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RewardRange {
+    pub priority: Priority,
+    pub min: Option<u64>,
+    pub max: Option<u64>,
+}
+
+// ==== Announcements ====
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Announcements {
+    pub announcements: Vec<Announcement>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct Announcement {
+    pub id: String,
+    pub title: String,
+    /// Markdown
+    #[serde(default)]
+    pub body: String,
+    #[serde(default, deserialize_with = "deserialize_optional_date")]
+    pub published_at: Option<DateTime<Utc>>,
+}
+
+// ==== Engagements ====
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EngagementListing {
+    pub engagements: Vec<Engagement>,
+    #[serde(rename = "paginationMeta")]
+    pub pagination_meta: PaginationMeta,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum EngagementKind {
+    BugBounty,
+    Vdp,
+}
+
+impl FromStr for EngagementKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "bug_bounty" | "bugbounty" => Ok(EngagementKind::BugBounty),
+            "vdp" => Ok(EngagementKind::Vdp),
+            other => Err(format!(
+                "unknown engagement type '{other}', expected bug_bounty or vdp"
+            )),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Engagement {
+    pub name: String,
+    /// `/engagements/{handle}`
+    pub brief_url: String,
+    #[serde(default)]
+    pub is_private: bool,
+    /// "bug_bounty" or "vdp"
+    pub category: Option<String>,
+}
+
+impl Engagement {
+    pub fn handle(&self) -> &str {
+        self.brief_url
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .unwrap_or_default()
+    }
+
+    pub fn kind(&self) -> Option<EngagementKind> {
+        self.category
+            .as_deref()
+            .and_then(|category| category.parse().ok())
+    }
+}
+
+// ==== Researcher Profile ====
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ResearcherProfile {
+    pub username: String,
+    /// Overall rank on the Bugcrowd leaderboard
+    pub rank: Option<u64>,
+    #[serde(default)]
+    pub stats: ResearcherStats,
+    #[serde(default)]
+    pub badges: Vec<Badge>,
+    #[serde(default)]
+    pub disclosures: Vec<DisclosedReport>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ResearcherStats {
+    #[serde(default, deserialize_with = "deserialize_null_default")]
+    pub points: u64,
+    #[serde(default, deserialize_with = "deserialize_null_default")]
+    pub accepted_submissions: u64,
+    pub accuracy: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Badge {
+    pub name: String,
+    pub description: Option<String>,
+}
+
+// ==== Session ====
+/// Response of `/auth/session`
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct AuthSession {
+    pub user: Option<SessionUser>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq, Eq)]
+pub struct SessionUser {
+    pub username: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(id: &str, disclosed_at: &str) -> DisclosedReport {
+        DisclosedReport {
+            id: id.to_string(),
+            disclosed_at: disclosed_at.parse::<DateTimeUtc>().unwrap().0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn priority_from_json() {
+        let priorities: Vec<Priority> =
+            serde_json::from_str(r#"[1, 4, 5, 0, null, "P2", "p3", "Critical"]"#).unwrap();
+        assert_eq!(
+            priorities,
+            [
+                Priority::P1,
+                Priority::P4,
+                Priority::P5,
+                Priority::Unknown,
+                Priority::Unknown,
+                Priority::P2,
+                Priority::P3,
+                Priority::Unknown
+            ]
+        );
+        assert_eq!(serde_json::to_string(&Priority::P2).unwrap(), "2");
+    }
+
+    #[test]
+    fn cursor_skips_seen_and_older() {
+        let mut cursor = DisclosureCursor::default();
+        cursor.advance(&[report("a", "2025-01-02T00:00:00Z")]);
+
+        assert!(!cursor.is_new(&report("a", "2025-01-02T00:00:00Z")));
+        assert!(!cursor.is_new(&report("old", "2025-01-01T00:00:00Z")));
+        assert!(cursor.is_new(&report("b", "2025-01-02T00:00:00Z")));
+        assert!(cursor.is_new(&report("c", "2025-01-03T00:00:00Z")));
+    }
+
+    #[test]
+    fn cursor_keeps_the_newest_ids() {
+        // Newest first, the last 20 share the newest timestamp
+        let reports: Vec<DisclosedReport> = (0..60)
+            .map(|i| {
+                let day = if i < 20 { 20 } else { 19 - i % 19 };
+                report(&format!("r{i}"), &format!("2025-01-{day:02}T00:00:00Z"))
+            })
+            .collect();
+
+        let mut cursor = DisclosureCursor::default();
+        cursor.advance(&reports);
+        assert_eq!(cursor.seen_ids.len(), 50);
+        assert_eq!(cursor.seen_ids[0], "r0");
+        assert!(reports.iter().all(|report| !cursor.is_new(report)));
+    }
+}
@@ -0,0 +1,267 @@
+use std::fmt;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Reward shown on the Crowdstream, e.g. "$1,500", "$500 - $1,000",
+/// "40 points" or "Kudos". The original text is kept in `raw`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Amount {
+    pub raw: String,
+    pub value: AmountValue,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AmountValue {
+    /// Values in cents, `min == max` unless a range was given
+    Money {
+        currency: String,
+        min: u64,
+        max: u64,
+    },
+    Points(u64),
+    Kudos,
+    /// Couldn't be parsed, only `raw` is meaningful
+    Unknown,
+}
+
+impl Amount {
+    /// Never fails, unrecognized amounts are [`AmountValue::Unknown`]. Amounts
+    /// without a currency are in USD, like the rest of Bugcrowd.
+    pub fn parse(raw: &str) -> Self {
+        Self {
+            raw: raw.to_string(),
+            value: AmountValue::parse(raw.trim()),
+        }
+    }
+
+    pub fn currency(&self) -> Option<&str> {
+        match &self.value {
+            AmountValue::Money { currency, .. } => Some(currency),
+            _ => None,
+        }
+    }
+
+    /// Lower bound in cents, for money amounts
+    pub fn min_cents(&self) -> Option<u64> {
+        match self.value {
+            AmountValue::Money { min, .. } => Some(min),
+            _ => None,
+        }
+    }
+
+    /// Upper bound in cents, for money amounts
+    pub fn max_cents(&self) -> Option<u64> {
+        match self.value {
+            AmountValue::Money { max, .. } => Some(max),
+            _ => None,
+        }
+    }
+}
+
+impl AmountValue {
+    fn parse(raw: &str) -> Self {
+        let lowercase = raw.to_lowercase();
+        if raw.is_empty() {
+            return Self::Unknown;
+        }
+        if lowercase.contains("kudos") {
+            return Self::Kudos;
+        }
+        if lowercase.contains("point") || lowercase.contains("pts") {
+            return match parse_number(&lowercase) {
+                Some(points) => Self::Points(points / 100),
+                None => Self::Unknown,
+            };
+        }
+
+        let normalized = raw
+            .replace(" to ", "-")
+            .replace(" TO ", "-")
+            .replace(['–', '—'], "-");
+        let mut bounds = vec![];
+        let mut currency = None;
+        for part in normalized.split('-') {
+            let Some(value) = parse_number(part) else {
+                return Self::Unknown;
+            };
+            match (currency.as_deref(), parse_currency(part).as_deref()) {
+                (Some(current), Some(part_currency)) if current != part_currency => {
+                    return Self::Unknown;
+                }
+                (None, part_currency) => currency = part_currency.map(str::to_string),
+                _ => {}
+            }
+            bounds.push(value);
+        }
+
+        match bounds[..] {
+            [value] => Self::Money {
+                currency: currency.unwrap_or_else(|| "USD".into()),
+                min: value,
+                max: value,
+            },
+            [min, max] => Self::Money {
+                currency: currency.unwrap_or_else(|| "USD".into()),
+                min: min.min(max),
+                max: min.max(max),
+            },
+            _ => Self::Unknown,
+        }
+    }
+}
+
+const CURRENCIES: [(&str, &str); 4] = [("$", "USD"), ("€", "EUR"), ("£", "GBP"), ("¥", "JPY")];
+
+/// Currency symbol, or an uppercase three letter code like "CHF"
+fn parse_currency(text: &str) -> Option<String> {
+    let symbol = CURRENCIES
+        .iter()
+        .find(|(symbol, _)| text.contains(symbol))
+        .map(|(_, code)| code.to_string());
+
+    symbol.or_else(|| {
+        text.split(|c: char| !c.is_ascii_alphabetic())
+            .find(|word| word.len() == 3 && word.chars().all(|c| c.is_ascii_uppercase()))
+            .map(str::to_string)
+    })
+}
+
+/// First number of `text` in hundredths, "1,500.50" is 150050 and "1.5k" is
+/// 150000, `None` if it doesn't fit
+fn parse_number(text: &str) -> Option<u64> {
+    let start = text.find(|c: char| c.is_ascii_digit())?;
+    let number: String = text[start..]
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == ',' || *c == '.')
+        .filter(|c| *c != ',')
+        .collect();
+    let suffix =
+        text[start..].trim_start_matches(|c: char| c.is_ascii_digit() || c == ',' || c == '.');
+    let multiplier = if suffix.trim_start().starts_with(['k', 'K']) {
+        1000
+    } else {
+        1
+    };
+
+    let (whole, fraction) = number.split_once('.').unwrap_or((&number, ""));
+    let whole: u64 = whole.parse().ok()?;
+    let fraction = format!("{fraction:0<2}");
+    let fraction: u64 = fraction.get(..2)?.parse().ok()?;
+    whole
+        .checked_mul(100)?
+        .checked_add(fraction)?
+        .checked_mul(multiplier)
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.value {
+            AmountValue::Money { currency, min, max } => {
+                write!(f, "{}", format_money(currency, *min))?;
+                if min != max {
+                    write!(f, " - {}", format_money(currency, *max))?;
+                }
+                Ok(())
+            }
+            AmountValue::Points(points) => write!(f, "{} points", group_thousands(*points)),
+            AmountValue::Kudos => write!(f, "Kudos"),
+            AmountValue::Unknown => write!(f, "{}", self.raw),
+        }
+    }
+}
+
+fn format_money(currency: &str, cents: u64) -> String {
+    let mut amount = group_thousands(cents / 100);
+    if !cents.is_multiple_of(100) {
+        amount = format!("{amount}.{:02}", cents % 100);
+    }
+
+    match CURRENCIES.iter().find(|(_, code)| *code == currency) {
+        Some((symbol, _)) => format!("{symbol}{amount}"),
+        None => format!("{amount} {currency}"),
+    }
+}
+
+fn group_thousands(value: u64) -> String {
+    let digits = value.to_string();
+    let mut grouped = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+impl Serialize for Amount {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.raw)
+    }
+}
+
+impl<'de> Deserialize<'de> for Amount {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Text(String),
+            Number(serde_json::Number),
+        }
+
+        Ok(match Raw::deserialize(deserializer)? {
+            Raw::Text(raw) => Amount::parse(&raw),
+            Raw::Number(raw) => Amount::parse(&raw.to_string()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn money(min: u64, max: u64) -> AmountValue {
+        AmountValue::Money {
+            currency: "USD".into(),
+            min,
+            max,
+        }
+    }
+
+    #[test]
+    fn parse_amounts() {
+        assert_eq!(Amount::parse("$1,500").value, money(150000, 150000));
+        assert_eq!(Amount::parse("$99.5").value, money(9950, 9950));
+        assert_eq!(Amount::parse("$500 - $1,000").value, money(50000, 100000));
+        assert_eq!(Amount::parse("$1k – $2.5k").value, money(100000, 250000));
+        assert_eq!(Amount::parse("250").value, money(25000, 25000));
+        assert_eq!(Amount::parse("40 points").value, AmountValue::Points(40));
+        assert_eq!(Amount::parse("Kudos").value, AmountValue::Kudos);
+        assert_eq!(Amount::parse("swag").value, AmountValue::Unknown);
+        assert_eq!(Amount::parse("$5 - €10").value, AmountValue::Unknown);
+
+        assert_eq!(Amount::parse("500 per bug").value, money(50000, 50000));
+        assert_eq!(
+            Amount::parse("$184467440737095517").value,
+            AmountValue::Unknown
+        );
+        assert_eq!(
+            Amount::parse("$18446744073709551k").value,
+            AmountValue::Unknown
+        );
+
+        let francs = Amount::parse("1500 CHF");
+        assert_eq!(francs.currency(), Some("CHF"));
+        assert_eq!(francs.min_cents(), Some(150000));
+    }
+
+    #[test]
+    fn display_amounts() {
+        assert_eq!(Amount::parse("$1500").to_string(), "$1,500");
+        assert_eq!(Amount::parse("$500-$1000").to_string(), "$500 - $1,000");
+        assert_eq!(Amount::parse("$12.50").to_string(), "$12.50");
+        assert_eq!(Amount::parse("2000 CHF").to_string(), "2,000 CHF");
+        assert_eq!(Amount::parse("1200 pts").to_string(), "1,200 points");
+        assert_eq!(Amount::parse("swag").to_string(), "swag");
+    }
+}
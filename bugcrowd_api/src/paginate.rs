@@ -0,0 +1,84 @@
+use std::{collections::HashSet, future::Future};
+
+use futures::{Stream, TryStreamExt, stream};
+use log::warn;
+
+/// Single page of a paginated endpoint
+pub(crate) struct Page<T> {
+    pub items: Vec<T>,
+    pub total_count: u64,
+}
+
+struct State {
+    page: u64,
+    yielded: u64,
+    seen: HashSet<String>,
+    done: bool,
+}
+
+/// Streams the items of a paginated endpoint, fetching pages only as the
+/// stream is polled. Items whose `key` was already yielded are skipped, and
+/// pagination stops on an empty page, a page made only of duplicates, once
+/// `total_count` items were yielded or after `max_pages` pages. Items without
+/// a key (e.g. anonymous heros) are never treated as duplicates.
+pub(crate) fn paginate<T, K, F, Fut>(
+    name: String,
+    max_pages: u64,
+    key: K,
+    fetch_page: F,
+) -> impl Stream<Item = Result<T, anyhow::Error>>
+where
+    K: Fn(&T) -> Option<String>,
+    F: FnMut(u64) -> Fut,
+    Fut: Future<Output = Result<Page<T>, anyhow::Error>>,
+{
+    let state = State {
+        page: 1,
+        yielded: 0,
+        seen: HashSet::new(),
+        done: max_pages == 0,
+    };
+
+    stream::try_unfold(
+        (state, key, fetch_page),
+        move |(mut state, key, mut fetch_page)| {
+            let name = name.clone();
+            async move {
+                if state.done {
+                    return Ok(None);
+                }
+
+                let page: Page<T> = fetch_page(state.page).await?;
+                let fetched = page.items.len();
+                let mut duplicates = 0;
+                let items: Vec<T> = page
+                    .items
+                    .into_iter()
+                    .filter(|item| {
+                        let duplicate = key(item).is_some_and(|key| !state.seen.insert(key));
+                        duplicates += duplicate as usize;
+                        !duplicate
+                    })
+                    .collect();
+                state.yielded += items.len() as u64;
+
+                if fetched == 0 {
+                    return Ok(None);
+                } else if items.is_empty() && duplicates > 0 {
+                    warn!("{name}: page {} only repeats earlier items", state.page);
+                    state.done = true;
+                } else if state.yielded >= page.total_count {
+                    state.done = true;
+                } else if state.page >= max_pages {
+                    warn!("{name}: page budget of {max_pages} exhausted");
+                    state.done = true;
+                }
+
+                state.page += 1;
+                Ok::<_, anyhow::Error>(Some((items, (state, key, fetch_page))))
+            }
+        },
+    )
+    .map_ok(|items| stream::iter(items.into_iter().map(Ok)))
+    .try_flatten()
+}
@@ -0,0 +1,232 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::ensure;
+use chrono::{DateTime, Utc};
+use log::warn;
+use reqwest::{Response, StatusCode, header::RETRY_AFTER};
+use tokio::{
+    sync::{OwnedSemaphorePermit, Semaphore},
+    time::{Instant, sleep_until},
+};
+
+pub const DEFAULT_REQUESTS_PER_SECOND: f64 = 2.0;
+pub const DEFAULT_MAX_CONCURRENCY: usize = 4;
+
+/// Pause used when a 429 doesn't say how long to back off
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(30);
+/// The rate is never throttled below this fraction of the configured rate
+const MIN_RATE_FRACTION: f64 = 1.0 / 16.0;
+/// Fraction of the configured rate recovered after each successful request
+const RECOVERY_FRACTION: f64 = 1.0 / 20.0;
+
+/// Token bucket shared by every clone of the client, capping both the request
+/// rate and the number of requests in flight. The rate is halved whenever
+/// Bugcrowd answers with a 429 and slowly recovers on successful requests.
+#[derive(Debug)]
+pub struct RateLimiter {
+    bucket: Mutex<Bucket>,
+    concurrency: Arc<Semaphore>,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_second: f64, max_concurrency: usize) -> Result<Self, anyhow::Error> {
+        ensure!(
+            requests_per_second.is_finite() && requests_per_second > 0.0,
+            "request rate must be positive, got {requests_per_second}"
+        );
+        ensure!(max_concurrency > 0, "max concurrency must be positive");
+
+        Ok(Self {
+            bucket: Mutex::new(Bucket::new(requests_per_second, Instant::now())),
+            concurrency: Arc::new(Semaphore::new(max_concurrency)),
+        })
+    }
+
+    /// Waits for a free request slot and a token. The request may be sent
+    /// while the returned permit is held.
+    pub async fn acquire(&self) -> OwnedSemaphorePermit {
+        let permit = self
+            .concurrency
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("rate limiter semaphore closed");
+
+        loop {
+            let ready_at = self.bucket.lock().unwrap().take(Instant::now());
+            match ready_at {
+                Some(ready_at) => sleep_until(ready_at).await,
+                None => return permit,
+            }
+        }
+    }
+
+    /// Adapts the rate to the response of a request
+    pub fn observe(&self, response: &Response) {
+        let mut bucket = self.bucket.lock().unwrap();
+        if response.status() == StatusCode::TOO_MANY_REQUESTS {
+            let retry_after = response
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| parse_retry_after(value, Utc::now()))
+                .unwrap_or(DEFAULT_RETRY_AFTER);
+
+            bucket.throttle(Instant::now(), retry_after);
+            warn!(
+                "rate limited by Bugcrowd ({}), slowing down to {:.2} requests/s",
+                response.url().path(),
+                bucket.rate
+            );
+        } else {
+            bucket.recover();
+        }
+    }
+
+    /// Current request rate, lower than configured after 429s
+    pub fn requests_per_second(&self) -> f64 {
+        self.bucket.lock().unwrap().rate
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(DEFAULT_REQUESTS_PER_SECOND, DEFAULT_MAX_CONCURRENCY).unwrap()
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    configured_rate: f64,
+    rate: f64,
+    tokens: f64,
+    refilled_at: Instant,
+    paused_until: Option<Instant>,
+}
+
+impl Bucket {
+    fn new(rate: f64, now: Instant) -> Self {
+        Self {
+            configured_rate: rate,
+            rate,
+            tokens: capacity(rate),
+            refilled_at: now,
+            paused_until: None,
+        }
+    }
+
+    /// Takes a token, or returns when one will be available
+    fn take(&mut self, now: Instant) -> Option<Instant> {
+        match self.paused_until {
+            Some(paused_until) if paused_until > now => return Some(paused_until),
+            Some(_) => self.paused_until = None,
+            None => {}
+        }
+
+        let elapsed = now.saturating_duration_since(self.refilled_at);
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * self.rate).min(capacity(self.rate));
+        self.refilled_at = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            None
+        } else {
+            let missing = 1.0 - self.tokens;
+            Some(now + Duration::from_secs_f64(missing / self.rate))
+        }
+    }
+
+    fn throttle(&mut self, now: Instant, retry_after: Duration) {
+        let min_rate = self.configured_rate * MIN_RATE_FRACTION;
+        self.rate = (self.rate / 2.0).max(min_rate);
+        self.tokens = 0.0;
+
+        let paused_until = now + retry_after;
+        self.paused_until = Some(match self.paused_until {
+            Some(current) => current.max(paused_until),
+            None => paused_until,
+        });
+    }
+
+    fn recover(&mut self) {
+        let step = self.configured_rate * RECOVERY_FRACTION;
+        self.rate = (self.rate + step).min(self.configured_rate);
+    }
+}
+
+/// `Retry-After` is either a number of seconds or an HTTP date
+fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (date.with_timezone(&Utc) - now)
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
+
+/// Bursts of up to one second worth of requests
+fn capacity(rate: f64) -> f64 {
+    rate.max(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bucket_throttles_and_recovers() {
+        let start = Instant::now();
+        let mut bucket = Bucket::new(2.0, start);
+
+        assert_eq!(bucket.take(start), None);
+        assert_eq!(bucket.take(start), None);
+        assert_eq!(bucket.take(start), Some(start + Duration::from_millis(500)));
+        assert_eq!(bucket.take(start + Duration::from_millis(500)), None);
+
+        let now = start + Duration::from_secs(1);
+        bucket.throttle(now, Duration::from_secs(10));
+        assert_eq!(bucket.rate, 1.0);
+        assert_eq!(bucket.take(now), Some(now + Duration::from_secs(10)));
+
+        for _ in 0..100 {
+            bucket.throttle(now, Duration::ZERO);
+        }
+        assert_eq!(bucket.rate, 2.0 * MIN_RATE_FRACTION);
+
+        for _ in 0..100 {
+            bucket.recover();
+        }
+        assert_eq!(bucket.rate, 2.0);
+    }
+
+    #[test]
+    fn parses_retry_after() {
+        let now = DateTime::parse_from_rfc3339("2025-01-02T03:04:05Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let parse = |value| parse_retry_after(value, now);
+
+        assert_eq!(parse(" 120 "), Some(Duration::from_secs(120)));
+        assert_eq!(
+            parse("Thu, 02 Jan 2025 03:05:05 GMT"),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(parse("Thu, 02 Jan 2025 03:00:00 GMT"), Some(Duration::ZERO));
+        assert_eq!(parse("soon"), None);
+    }
+
+    #[test]
+    fn rejects_invalid_limits() {
+        assert!(RateLimiter::new(0.0, 4).is_err());
+        assert!(RateLimiter::new(f64::NAN, 4).is_err());
+        assert!(RateLimiter::new(2.0, 0).is_err());
+        assert!(RateLimiter::new(0.5, 1).is_ok());
+    }
+}
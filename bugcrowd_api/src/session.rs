@@ -0,0 +1,278 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+use log::{debug, error, info, warn};
+use reqwest::{Client, Method, StatusCode, Url, cookie::CookieStore, header::HeaderValue};
+use tokio::{
+    sync::{Mutex, watch},
+    task::AbortHandle,
+};
+
+use crate::{
+    auth::{self, Credentials},
+    models::{AuthSession, SessionUser},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SessionState {
+    /// The session endpoint hasn't answered yet
+    Unknown,
+    Authenticated(SessionUser),
+    /// No session token configured, or it expired
+    Unauthenticated,
+}
+
+pub(crate) const SESSION_COOKIE: &str = "_bugcrowd_session";
+
+/// Cookie store of the client. The session token can be swapped at runtime,
+/// and refreshes sent back by Bugcrowd are published to `token`. Cookies are
+/// only sent to and accepted from the host of the base URL.
+#[derive(Debug)]
+pub(crate) struct SessionCookies {
+    cookies: RwLock<BTreeMap<String, String>>,
+    token: watch::Sender<Option<String>>,
+    origin: Option<(String, Option<u16>)>,
+}
+
+impl SessionCookies {
+    pub(crate) fn new(session_token: Option<String>, base_url: &str) -> Self {
+        let mut cookies = BTreeMap::new();
+        if let Some(session_token) = &session_token {
+            cookies.insert(SESSION_COOKIE.to_string(), session_token.clone());
+        }
+
+        Self {
+            cookies: RwLock::new(cookies),
+            token: watch::Sender::new(session_token),
+            origin: Url::parse(base_url).ok().as_ref().and_then(Self::origin),
+        }
+    }
+
+    fn origin(url: &Url) -> Option<(String, Option<u16>)> {
+        let host = url.host_str()?.to_lowercase();
+        Some((host, url.port_or_known_default()))
+    }
+
+    fn is_same_origin(&self, url: &Url) -> bool {
+        self.origin.is_some() && self.origin == Self::origin(url)
+    }
+
+    pub(crate) fn set_session_token(&self, session_token: String) {
+        self.set_cookie(SESSION_COOKIE, &session_token);
+    }
+
+    pub(crate) fn token(&self) -> watch::Receiver<Option<String>> {
+        self.token.subscribe()
+    }
+
+    fn set_cookie(&self, name: &str, value: &str) {
+        let mut cookies = self.cookies.write().unwrap();
+        if value.is_empty() {
+            cookies.remove(name);
+        } else {
+            cookies.insert(name.to_string(), value.to_string());
+        }
+
+        if name == SESSION_COOKIE {
+            let token = (!value.is_empty()).then(|| value.to_string());
+            self.token.send_if_modified(|current| {
+                if *current == token {
+                    return false;
+                }
+
+                debug!("session token updated");
+                *current = token;
+                true
+            });
+        }
+    }
+}
+
+impl CookieStore for SessionCookies {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        if !self.is_same_origin(url) {
+            return;
+        }
+
+        for header in cookie_headers {
+            let Ok(header) = header.to_str() else {
+                continue;
+            };
+
+            let cookie = header.split(';').next().unwrap_or_default();
+            if let Some((name, value)) = cookie.split_once('=') {
+                let expired = header.to_lowercase().contains("max-age=0");
+                self.set_cookie(name.trim(), if expired { "" } else { value.trim() });
+            }
+        }
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        let cookies = self.cookies.read().unwrap();
+        if cookies.is_empty() || !self.is_same_origin(url) {
+            return None;
+        }
+
+        let cookies = cookies
+            .iter()
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<_>>()
+            .join("; ");
+        HeaderValue::from_str(&cookies).ok()
+    }
+}
+
+/// Keeps the session alive in the background for as long as it isn't dropped
+#[derive(Debug)]
+pub(crate) struct SessionKeepalive {
+    state: watch::Receiver<SessionState>,
+    task: AbortHandle,
+}
+
+impl SessionKeepalive {
+    pub(crate) fn spawn(
+        request_client: Client,
+        base_url: Arc<str>,
+        credentials: Option<Arc<Mutex<Credentials>>>,
+    ) -> Self {
+        let (sender, state) = watch::channel(SessionState::Unknown);
+        let task = tokio::task::spawn(__keepalive_session_background(
+            request_client,
+            base_url,
+            credentials,
+            sender,
+        ));
+
+        Self {
+            state,
+            task: task.abort_handle(),
+        }
+    }
+
+    pub(crate) fn state(&self) -> watch::Receiver<SessionState> {
+        self.state.clone()
+    }
+}
+
+impl Drop for SessionKeepalive {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn __keepalive_session_background(
+    request_client: Client,
+    base_url: Arc<str>,
+    credentials: Option<Arc<Mutex<Credentials>>>,
+    state: watch::Sender<SessionState>,
+) {
+    const PING_SESSION_INTERVAL: Duration = Duration::from_secs(60);
+    const LOGIN_RETRY_INTERVAL: Duration = Duration::from_secs(60 * 15);
+    let request = request_client
+        .request(
+            Method::GET,
+            format!("{base_url}/auth/session?update_activity=true"),
+        )
+        .build()
+        .unwrap();
+    let mut last_login: Option<Instant> = None;
+
+    loop {
+        let request = request.try_clone().unwrap();
+        match request_client.execute(request).await {
+            Err(session_ping_error) => {
+                error!("failed to keep session alive: {session_ping_error:#?}");
+            }
+            Ok(response) => {
+                debug!("pinged session");
+                let new_state = match response.status() {
+                    StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                        Some(SessionState::Unauthenticated)
+                    }
+                    status if status.is_success() => match response.json::<AuthSession>().await {
+                        Ok(AuthSession { user: Some(user) }) => {
+                            Some(SessionState::Authenticated(user))
+                        }
+                        Ok(AuthSession { user: None }) => Some(SessionState::Unauthenticated),
+                        Err(parse_error) => {
+                            warn!("unexpected session response: {parse_error:#?}");
+                            None
+                        }
+                    },
+                    status => {
+                        warn!("unexpected session status {status}");
+                        None
+                    }
+                };
+
+                if let Some(new_state) = new_state {
+                    state.send_if_modified(|state| {
+                        if *state == new_state {
+                            return false;
+                        }
+
+                        info!("session state changed to {new_state:?}");
+                        *state = new_state;
+                        true
+                    });
+                }
+            }
+        }
+
+        let unauthenticated = *state.borrow() == SessionState::Unauthenticated;
+        let can_login = last_login.is_none_or(|last| last.elapsed() >= LOGIN_RETRY_INTERVAL);
+        if let Some(credentials) = credentials
+            .as_ref()
+            .filter(|_| unauthenticated && can_login)
+        {
+            last_login = Some(Instant::now());
+            match auth::login(&request_client, &base_url, credentials).await {
+                Ok(()) => continue,
+                Err(login_error) => error!("failed to sign in again: {login_error:#?}"),
+            }
+        }
+
+        tokio::time::sleep(PING_SESSION_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_cookie_refresh() {
+        let url = Url::parse("https://bugcrowd.com/").unwrap();
+        let cookies = SessionCookies::new(Some("old".to_string()), "https://bugcrowd.com");
+        let token = cookies.token();
+
+        let headers = [
+            HeaderValue::from_static("_bugcrowd_session=new; path=/; HttpOnly"),
+            HeaderValue::from_static("csrf=abc; path=/"),
+        ];
+        cookies.set_cookies(&mut headers.iter(), &url);
+
+        assert_eq!(token.borrow().as_deref(), Some("new"));
+        assert_eq!(
+            cookies.cookies(&url).unwrap(),
+            "_bugcrowd_session=new; csrf=abc"
+        );
+    }
+
+    #[test]
+    fn cookies_stay_on_the_base_url_host() {
+        let cookies = SessionCookies::new(Some("token".to_string()), "https://bugcrowd.com");
+        let other = Url::parse("https://attacker.example/").unwrap();
+        let other_port = Url::parse("https://bugcrowd.com:8443/").unwrap();
+        assert!(cookies.cookies(&other).is_none());
+        assert!(cookies.cookies(&other_port).is_none());
+
+        let headers = [HeaderValue::from_static("_bugcrowd_session=stolen; path=/")];
+        cookies.set_cookies(&mut headers.iter(), &other);
+        let url = Url::parse("https://BUGCROWD.com/engagements").unwrap();
+        assert_eq!(cookies.cookies(&url).unwrap(), "_bugcrowd_session=token");
+    }
+}
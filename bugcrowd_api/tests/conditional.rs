@@ -0,0 +1,59 @@
+use bugcrowd_api::{cache::Polled, client::BugcrowdApi};
+use wiremock::{
+    Mock, MockServer, ResponseTemplate,
+    matchers::{header, method, path, query_param},
+};
+
+const HALL_OF_FAME: &str = r#"{
+    "pagination_meta": {"limit": 25, "totalCount": 1},
+    "presented_heroes": {
+        "heroes": [{"accentColor": null, "avatar": "", "points": 40, "profileUrl": "/h/alice", "username": "alice"}],
+        "hide_points": false,
+        "total": 1
+    }
+}"#;
+
+#[tokio::test]
+async fn unchanged_hall_of_fame() {
+    let server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/engagements/acme/hall_of_fames.json"))
+        .and(query_param("page", "1"))
+        .and(header("if-none-match", "\"v1\""))
+        .respond_with(ResponseTemplate::new(304))
+        .with_priority(1)
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/engagements/acme/hall_of_fames.json"))
+        .and(query_param("page", "1"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("etag", "\"v1\"")
+                .set_body_string(HALL_OF_FAME),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let bugcrowd = BugcrowdApi::builder().base_url(server.uri()).build();
+
+    let revision = match bugcrowd.hall_of_fame_since("acme", None).await.unwrap() {
+        Polled::Changed(heros, revision) => {
+            assert_eq!(heros.len(), 1);
+            assert_eq!(heros[0].rank, 1);
+            revision
+        }
+        Polled::Unchanged => panic!("first poll can't be unchanged"),
+    };
+
+    let polled = bugcrowd.hall_of_fame_since("acme", Some(revision)).await;
+    assert!(matches!(polled.unwrap(), Polled::Unchanged));
+
+    // A poller that didn't see the first response still gets the heros
+    let heros = bugcrowd.hall_of_fame("acme").await.unwrap();
+    assert_eq!(heros[0].username, "alice");
+}
@@ -0,0 +1,110 @@
+use bugcrowd_api::{auth::Credentials, client::BugcrowdApi};
+use wiremock::{
+    Mock, MockServer, ResponseTemplate,
+    matchers::{body_string_contains, header, header_regex, method, path},
+};
+
+const SIGN_IN_PAGE: &str =
+    r#"<html><head><meta name="csrf-token" content="csrf-1" /></head></html>"#;
+const OTP_PAGE: &str = r#"<html><head><meta name="csrf-token" content="csrf-2" /></head>
+<form action="/auth/otp-challenge"><input name="otp_code" /></form></html>"#;
+
+#[tokio::test]
+async fn login_with_otp() {
+    let server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/user/sign_in"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("set-cookie", "_bugcrowd_session=anonymous; path=/")
+                .set_body_string(SIGN_IN_PAGE),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/user/sign_in"))
+        .and(header("x-csrf-token", "csrf-1"))
+        .and(body_string_contains("user%5Bemail%5D=alice%40example.com"))
+        .and(body_string_contains("user%5Bpassword%5D=hunter2"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(OTP_PAGE))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/auth/otp-challenge"))
+        .and(header("x-csrf-token", "csrf-2"))
+        .and(body_string_contains("otp_code="))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .insert_header("set-cookie", "_bugcrowd_session=signed-in; path=/"),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/auth/session"))
+        .and(header_regex("cookie", "_bugcrowd_session=signed-in"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_string(r#"{"user": {"username": "alice"}}"#),
+        )
+        .with_priority(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/auth/session"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"user": null}"#))
+        .mount(&server)
+        .await;
+
+    let api = BugcrowdApi::builder()
+        .base_url(server.uri())
+        .credentials(Some(Credentials {
+            email: "alice@example.com".to_string(),
+            password: "hunter2".to_string(),
+            totp_secret: Some("JBSWY3DPEHPK3PXP".to_string()),
+        }))
+        .build();
+
+    api.login().await.unwrap();
+    assert_eq!(api.session_token().borrow().as_deref(), Some("signed-in"));
+}
+
+#[tokio::test]
+async fn login_rejected() {
+    let server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path("/user/sign_in"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(SIGN_IN_PAGE))
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/user/sign_in"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(SIGN_IN_PAGE))
+        .mount(&server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path("/auth/session"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"user": null}"#))
+        .mount(&server)
+        .await;
+
+    let api = BugcrowdApi::builder()
+        .base_url(server.uri())
+        .credentials(Some(Credentials {
+            email: "alice@example.com".to_string(),
+            password: "wrong".to_string(),
+            totp_secret: None,
+        }))
+        .build();
+
+    assert!(api.login().await.is_err());
+}
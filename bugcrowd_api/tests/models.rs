@@ -0,0 +1,70 @@
+use bugcrowd_api::models::{
+    ActivityKind, CrowdStream, CrowdStreamActivities, HallOfFame, Priority,
+};
+
+const CROWDSTREAM_DISCLOSURES: &str = include_str!("fixtures/crowdstream_disclosures.json");
+const CROWDSTREAM: &str = include_str!("fixtures/crowdstream.json");
+const HALL_OF_FAME: &str = include_str!("fixtures/hall_of_fame.json");
+
+#[test]
+fn disclosed_reports() {
+    let crowdstream: CrowdStream = serde_json::from_str(CROWDSTREAM_DISCLOSURES).unwrap();
+    assert_eq!(crowdstream.pagination_meta.total_count, 2);
+    assert!(crowdstream.pagination_meta.extra.is_empty());
+
+    let [xss, idor] = &crowdstream.results[..] else {
+        panic!("expected two reports");
+    };
+    assert_eq!(xss.priority, Priority::P2);
+    assert_eq!(xss.extra["substate"], "resolved");
+    assert_eq!(xss.extra["engagement_path"], "/engagements/acme");
+    assert_eq!(xss.amount.as_ref().unwrap().max_cents(), Some(150000));
+    assert_eq!(
+        xss.created_at.unwrap().to_rfc3339(),
+        "2025-02-27T18:04:11+00:00"
+    );
+
+    assert_eq!(idor.priority, Priority::Unknown);
+    assert!(idor.amount.is_none());
+    assert!(idor.created_at.is_none());
+    assert_eq!(idor.disclosed_at.to_rfc3339(), "2025-04-09T08:00:00+00:00");
+}
+
+#[test]
+fn invalid_disclosure_date_skips_the_report() {
+    let fixture = CROWDSTREAM_DISCLOSURES.replace("2025-04-10T14:30:00.000Z", "not a date");
+    let crowdstream: CrowdStream = serde_json::from_str(&fixture).unwrap();
+    assert_eq!(crowdstream.results.len(), 1);
+    assert_eq!(crowdstream.results[0].title, "IDOR on invoice export");
+}
+
+#[test]
+fn crowdstream_activities() {
+    let activities: CrowdStreamActivities = serde_json::from_str(CROWDSTREAM).unwrap();
+    let kinds: Vec<ActivityKind> = activities.results.iter().map(|a| a.kind()).collect();
+    assert_eq!(
+        kinds,
+        [
+            ActivityKind::Accepted,
+            ActivityKind::Rewarded,
+            ActivityKind::Disclosed
+        ]
+    );
+    assert_eq!(
+        activities.results[1].amount.as_ref().unwrap().to_string(),
+        "$500 - $750"
+    );
+}
+
+#[test]
+fn hall_of_fame() {
+    let hall_of_fame: HallOfFame = serde_json::from_str(HALL_OF_FAME).unwrap();
+    let heros = &hall_of_fame.presented_heroes.heroes;
+
+    assert!(!hall_of_fame.presented_heroes.hide_points);
+    assert_eq!(heros.len(), 3);
+    assert_eq!(heros[0].profile_url.as_deref(), Some("/h/alice"));
+    assert_eq!(heros[0].extra["isCurrentUser"], false);
+    assert_eq!(heros[1].points, 0);
+    assert_eq!(heros[2].points, 140);
+}
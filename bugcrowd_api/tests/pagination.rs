@@ -0,0 +1,67 @@
+use bugcrowd_api::client::BugcrowdApi;
+use futures::{StreamExt, TryStreamExt};
+use wiremock::{
+    Mock, MockServer, ResponseTemplate,
+    matchers::{method, path, query_param},
+};
+
+fn hall_of_fame_page(usernames: &[&str], total_count: u64) -> String {
+    let heroes: Vec<String> = usernames
+        .iter()
+        .map(|username| {
+            format!(
+                r#"{{"accentColor": null, "avatar": "", "points": 10, "profileUrl": null, "username": "{username}"}}"#
+            )
+        })
+        .collect();
+
+    format!(
+        r#"{{
+            "pagination_meta": {{"limit": 2, "totalCount": {total_count}}},
+            "presented_heroes": {{"heroes": [{}], "hide_points": false, "total": {total_count}}}
+        }}"#,
+        heroes.join(",")
+    )
+}
+
+async fn mount_page(server: &MockServer, page: &str, body: String, expected_calls: u64) {
+    Mock::given(method("GET"))
+        .and(path("/engagements/acme/hall_of_fames.json"))
+        .and(query_param("page", page))
+        .respond_with(ResponseTemplate::new(200).set_body_string(body))
+        .expect(expected_calls)
+        .mount(server)
+        .await;
+}
+
+#[tokio::test]
+async fn stops_on_repeated_page() {
+    let server = MockServer::start().await;
+    // The count drifted, Bugcrowd keeps answering with the last page
+    mount_page(&server, "1", hall_of_fame_page(&["alice", "bob"], 10), 1).await;
+    mount_page(&server, "2", hall_of_fame_page(&["carol"], 10), 1).await;
+    mount_page(&server, "3", hall_of_fame_page(&["carol"], 10), 1).await;
+
+    let bugcrowd = BugcrowdApi::builder().base_url(server.uri()).build();
+    let heros = bugcrowd.hall_of_fame("acme").await.unwrap();
+
+    let usernames: Vec<&str> = heros.iter().map(|h| h.username.as_str()).collect();
+    assert_eq!(usernames, ["alice", "bob", "carol"]);
+}
+
+#[tokio::test]
+async fn fetches_pages_lazily() {
+    let server = MockServer::start().await;
+    mount_page(&server, "1", hall_of_fame_page(&["alice", "bob"], 4), 1).await;
+    mount_page(&server, "2", hall_of_fame_page(&["carol", "dave"], 4), 0).await;
+
+    let bugcrowd = BugcrowdApi::builder().base_url(server.uri()).build();
+    let heros: Vec<_> = bugcrowd
+        .hall_of_fame_stream("acme", 10)
+        .take(2)
+        .try_collect()
+        .await
+        .unwrap();
+
+    assert_eq!(heros.len(), 2);
+}
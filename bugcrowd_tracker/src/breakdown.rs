@@ -1,98 +1,473 @@
-static CRITICAL: i32 = 40;
-static HIGH: i32 = 20;
-static MODERATE: i32 = 10;
-static LOW: i32 = 5;
+use std::{fmt, str::FromStr};
 
-#[derive(Default, Debug, PartialEq, Eq)]
-pub struct PointsBreakdown {
-    critical_bounty: i32,
-    high_bounty: i32,
-    moderate_bounty: i32,
-    low_bounty: i32,
-}
-
-impl ToString for PointsBreakdown {
-    fn to_string(&self) -> String {
-        let mut parts = Vec::new();
-
-        macro_rules! add_part {
-            ($condition:expr, $text:expr, $count:expr) => {
-                if $condition > 0 {
-                    parts.push(if $count < 2 {
-                        $text.to_string()
-                    } else {
-                        format!("{}({})", $text, $count)
-                    });
-                }
-            };
+use bugcrowd_api::models::Priority;
+
+/// Upper bound of submissions a single points change is split into
+const MAX_SUBMISSIONS: i32 = 12;
+/// How much less likely each additional submission within a single poll is
+const ADDITIONAL_SUBMISSION_ODDS: f64 = 0.1;
+/// Below this likelihood the most likely breakdown is reported as ambiguous
+const UNAMBIGUOUS_LIKELIHOOD: f64 = 0.8;
+/// Alternatives listed when the breakdown is ambiguous
+const MAX_ALTERNATIVES: usize = 3;
+/// Share of point losses caused by an accepted report being taken back
+/// (retracted, or marked as a duplicate later on) rather than an N/A penalty
+const RETRACTION_SHARE: f64 = 0.4;
+/// Points lost for a submission marked not applicable, unless configured
+const NOT_APPLICABLE_PENALTY: i32 = 5;
+
+/// What a single submission did to a researcher's points
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Accepted(Priority),
+    /// Points of an accepted report were taken back
+    Retracted(Priority),
+    NotApplicable,
+    /// P5 and other outcomes that aren't awarded points, only named when a
+    /// report like that was disclosed and never counted toward the points
+    Informational,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Accepted(priority) => f.write_str(priority.name()),
+            Outcome::Retracted(priority) => write!(f, "retracted {}", priority.name()),
+            Outcome::NotApplicable => f.write_str("N/A penalty"),
+            Outcome::Informational => f.write_str(Priority::P5.name()),
         }
+    }
+}
+
+/// Points awarded per priority, Bugcrowd's defaults unless the program uses
+/// its own scheme
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PointsTable {
+    points: [(Priority, i32); 4],
+    /// Points lost, positive
+    not_applicable: i32,
+}
 
-        add_part!(self.critical_bounty, "Critical", self.critical_bounty);
-        add_part!(self.high_bounty, "High", self.high_bounty);
-        add_part!(self.moderate_bounty, "Medium", self.moderate_bounty);
-        add_part!(self.low_bounty, "Low", self.low_bounty);
+impl PointsTable {
+    /// Table configured for `program`, falling back to the table configured
+    /// without a program and then to the defaults
+    pub fn for_program(tables: &[ProgramPointsTable], program: &str) -> PointsTable {
+        tables
+            .iter()
+            .find(|t| t.program.as_deref() == Some(program))
+            .or_else(|| tables.iter().find(|t| t.program.is_none()))
+            .map(|t| t.table.clone())
+            .unwrap_or_default()
+    }
+}
 
-        parts.join(", ")
+impl Default for PointsTable {
+    fn default() -> Self {
+        Self {
+            points: Priority::REWARDED.map(|priority| (priority, priority.points())),
+            not_applicable: NOT_APPLICABLE_PENALTY,
+        }
     }
 }
 
-pub fn calculate_points_breakdown(mut points: i32) -> Option<PointsBreakdown> {
-    let mut breakdown = PointsBreakdown::default();
+/// "p1=50,p2=25,na=1", entries left out keep their default points
+impl FromStr for PointsTable {
+    type Err = String;
 
-    let thresholds = [CRITICAL, HIGH, MODERATE, LOW];
-    let mut index = 0;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut table = PointsTable::default();
+        for entry in s.split(',').filter(|entry| !entry.trim().is_empty()) {
+            let (priority, points) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("expected priority=points, got {entry:?}"))?;
+            let points: i32 = points
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid points {points:?}"))?;
 
-    while index < thresholds.len() {
-        let is_over = {
-            if thresholds[index] > 0 {
-                points >= thresholds[index]
-            } else {
-                points <= thresholds[index]
+            if priority.trim().eq_ignore_ascii_case("na") {
+                // Either sign is accepted, it's always a penalty
+                table.not_applicable = points.abs();
+                continue;
             }
+
+            let priority: Priority = priority.parse()?;
+
+            match table.points.iter_mut().find(|(p, _)| *p == priority) {
+                Some(entry) if points > 0 => entry.1 = points,
+                Some(_) => return Err(format!("{priority} points must be positive")),
+                None => return Err(format!("{priority} isn't awarded points")),
+            }
+        }
+        Ok(table)
+    }
+}
+
+/// Points table of a single program, or of every program without `program:`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgramPointsTable {
+    pub program: Option<String>,
+    pub table: PointsTable,
+}
+
+/// "acme:p1=50,p2=25" or "p1=50,p2=25"
+impl FromStr for ProgramPointsTable {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (program, table) = match s.split_once(':') {
+            Some((program, table)) => (Some(program.trim().to_string()), table),
+            None => (None, s),
         };
+        Ok(Self {
+            program,
+            table: table.parse()?,
+        })
+    }
+}
 
-        if is_over {
-            let count = points / thresholds[index];
-            match index {
-                0 => breakdown.critical_bounty = count,
-                1 => breakdown.high_bounty = count,
-                2 => breakdown.moderate_bounty = count,
-                3 => breakdown.low_bounty = count,
-                _ => {}
+/// How often each priority shows up among a program's recent disclosures
+#[derive(Debug, Clone, Default)]
+pub struct PriorityWeights {
+    counts: [u32; 4],
+}
+
+impl PriorityWeights {
+    pub fn from_priorities(priorities: impl IntoIterator<Item = Priority>) -> Self {
+        let mut weights = Self::default();
+        for priority in priorities {
+            if let Some(index) = Priority::REWARDED.iter().position(|p| *p == priority) {
+                weights.counts[index] += 1;
             }
+        }
+        weights
+    }
+
+    /// Share of `priority`, smoothed so unseen priorities stay possible
+    fn probability(&self, priority: Priority) -> f64 {
+        let index = Priority::REWARDED.iter().position(|p| *p == priority);
+        let count = index.map(|index| self.counts[index]).unwrap_or(0);
+        let total: u32 = self.counts.iter().sum();
+        (count as f64 + 1.0) / (total as f64 + self.counts.len() as f64)
+    }
+}
+
+/// Number of submissions per outcome, gains then losses, most severe first
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct PointsBreakdown {
+    counts: Vec<(Outcome, i32)>,
+    /// Relative to the other breakdowns of the same points
+    pub likelihood: f64,
+}
+
+impl fmt::Display for PointsBreakdown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = self
+            .counts
+            .iter()
+            .map(|(outcome, count)| {
+                if *count < 2 {
+                    outcome.to_string()
+                } else {
+                    format!("{outcome}({count})")
+                }
+            })
+            .collect();
+
+        f.write_str(&parts.join(", "))
+    }
+}
+
+/// Every way of splitting `points` into submissions, most likely first.
+/// Accepted submissions add points, retracted reports and N/A penalties take
+/// them away, and a change can mix both (+35 is a Critical and an N/A
+/// penalty). Fewer submissions are favored, and priorities that are common
+/// among the program's recent disclosures are more likely. Past
+/// [`MAX_SUBMISSIONS`] the [`greedy`] breakdown is returned.
+pub fn breakdowns(
+    points: i32,
+    table: &PointsTable,
+    weights: &PriorityWeights,
+) -> Vec<PointsBreakdown> {
+    if points == 0 {
+        return vec![];
+    }
+
+    let mut decompositions = vec![];
+    decompose(points, &outcomes(table), &mut vec![], &mut decompositions);
+    if decompositions.is_empty() {
+        return greedy(points, table).into_iter().collect();
+    }
+
+    let mut breakdowns: Vec<PointsBreakdown> = decompositions
+        .into_iter()
+        .map(|counts| {
+            let likelihood = likelihood(&counts, weights);
+            PointsBreakdown { counts, likelihood }
+        })
+        .collect();
+
+    let total: f64 = breakdowns.iter().map(|b| b.likelihood).sum();
+    for breakdown in &mut breakdowns {
+        breakdown.likelihood /= total;
+    }
+    breakdowns.sort_by(|a, b| b.likelihood.total_cmp(&a.likelihood));
+    breakdowns
+}
+
+/// Largest outcomes of the same sign as `points` first, without bounding the
+/// submissions. Used when there are too many submissions to enumerate, and
+/// for heroes new to the hall of fame whose points add up over their whole
+/// history
+pub fn greedy(points: i32, table: &PointsTable) -> Option<PointsBreakdown> {
+    let mut outcomes: Vec<(Outcome, i32)> = outcomes(table)
+        .into_iter()
+        .filter(|(_, value)| value.signum() == points.signum())
+        .collect();
+    outcomes.sort_by_key(|(_, value)| -value.abs());
 
-            points -= count * thresholds[index];
+    let mut remaining = points.abs();
+    let mut counts = vec![];
+    for (outcome, value) in outcomes {
+        let count = remaining / value.abs();
+        if count > 0 {
+            counts.push((outcome, count));
+            remaining -= count * value.abs();
         }
+    }
+
+    (remaining == 0 && !counts.is_empty()).then_some(PointsBreakdown {
+        counts,
+        likelihood: 1.0,
+    })
+}
+
+/// Points each outcome adds, negative for losses, most severe first
+fn outcomes(table: &PointsTable) -> Vec<(Outcome, i32)> {
+    let accepted = table.points.iter();
+    let retracted = table.points.iter();
+    accepted
+        .map(|(priority, points)| (Outcome::Accepted(*priority), *points))
+        .chain(retracted.map(|(priority, points)| (Outcome::Retracted(*priority), -points)))
+        .chain([(Outcome::NotApplicable, -table.not_applicable)])
+        .filter(|(_, points)| *points != 0)
+        .collect()
+}
+
+fn decompose(
+    points: i32,
+    outcomes: &[(Outcome, i32)],
+    counts: &mut Vec<(Outcome, i32)>,
+    decompositions: &mut Vec<Vec<(Outcome, i32)>>,
+) {
+    let submissions: i32 = counts.iter().map(|(_, count)| count).sum();
+    let Some(((outcome, value), rest)) = outcomes.split_first() else {
+        if points == 0 && submissions > 0 {
+            decompositions.push(counts.clone());
+        }
+        return;
+    };
+
+    // The remaining submissions can't make up for the points left
+    let largest = outcomes.iter().map(|(_, value)| value.abs()).max();
+    if points.abs() > largest.unwrap_or(0) * (MAX_SUBMISSIONS - submissions) {
+        return;
+    }
+
+    // Taking back a report accepted in the same change cancels out
+    let cancels = |(counted, _): &(Outcome, i32)| match (counted, outcome) {
+        (Outcome::Accepted(accepted), Outcome::Retracted(retracted)) => accepted == retracted,
+        _ => false,
+    };
+    let max_count = if counts.iter().any(cancels) {
+        0
+    } else {
+        MAX_SUBMISSIONS - submissions
+    };
+
+    for count in (0..=max_count).rev() {
+        if count > 0 {
+            counts.push((*outcome, count));
+        }
+        decompose(points - count * value, rest, counts, decompositions);
+        if count > 0 {
+            counts.pop();
+        }
+    }
+}
+
+/// Multinomial probability of the counts, weighted down for every submission
+/// past the first
+fn likelihood(counts: &[(Outcome, i32)], weights: &PriorityWeights) -> f64 {
+    let factorial = |n: i32| (1..=n).map(f64::from).product::<f64>();
+    let submissions: i32 = counts.iter().map(|(_, count)| count).sum();
+
+    let mut likelihood = ADDITIONAL_SUBMISSION_ODDS.powi(submissions - 1) * factorial(submissions);
+    for (outcome, count) in counts {
+        let probability = match outcome {
+            Outcome::Accepted(priority) => weights.probability(*priority),
+            Outcome::Retracted(priority) => RETRACTION_SHARE * weights.probability(*priority),
+            Outcome::NotApplicable => 1.0 - RETRACTION_SHARE,
+            Outcome::Informational => 1.0,
+        };
+        likelihood *= probability.powi(*count) / factorial(*count);
+    }
+    likelihood
+}
 
-        index += 1;
+/// Adds `count` zero-point submissions to every breakdown, e.g. for P5
+/// reports disclosed along with the points change
+pub fn with_informational(breakdowns: &mut [PointsBreakdown], count: i32) {
+    if count > 0 {
+        for breakdown in breakdowns {
+            breakdown.counts.push((Outcome::Informational, count));
+        }
     }
+}
 
-    if points != 0 || breakdown == PointsBreakdown::default() {
-        return None;
+/// Most likely breakdown, or the likely alternatives when it's ambiguous
+pub fn describe(breakdowns: &[PointsBreakdown]) -> Option<String> {
+    let most_likely = breakdowns.first()?;
+    if most_likely.likelihood >= UNAMBIGUOUS_LIKELIHOOD {
+        return Some(most_likely.to_string());
     }
 
-    Some(breakdown)
+    let alternatives: Vec<String> = breakdowns
+        .iter()
+        .take(MAX_ALTERNATIVES)
+        .map(|b| format!("{} {:.0}%", b, b.likelihood * 100.0))
+        .collect();
+    Some(format!("ambiguous: {}", alternatives.join(" or ")))
+}
+
+/// Explanation of lost points, e.g. "likely -5: N/A penalty"
+pub fn describe_loss(lost: u64, breakdowns: &[PointsBreakdown]) -> Option<String> {
+    let description = describe(breakdowns)?;
+    Some(match description.strip_prefix("ambiguous: ") {
+        Some(alternatives) => format!("-{lost} is ambiguous: {alternatives}"),
+        None => format!("likely -{lost}: {description}"),
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn most_likely(points: i32) -> Option<String> {
+        breakdowns(points, &PointsTable::default(), &PriorityWeights::default())
+            .first()
+            .map(|b| b.to_string())
+    }
+
     #[test]
     fn simple() {
-        let a = calculate_points_breakdown(40);
-        let b = calculate_points_breakdown(40 + 40);
+        assert_eq!(most_likely(40).unwrap(), "Critical");
+        assert_eq!(most_likely(40 + 40).unwrap(), "Critical(2)");
+    }
+
+    #[test]
+    fn gains_and_losses_mixed() {
+        assert_eq!(most_likely(35).unwrap(), "Critical, N/A penalty");
+        assert_eq!(most_likely(-35).unwrap(), "Low, retracted Critical");
+
+        let breakdowns = breakdowns(15, &PointsTable::default(), &PriorityWeights::default());
+        assert!(
+            breakdowns
+                .iter()
+                .any(|b| b.to_string() == "High, N/A penalty")
+        );
+        // Never a report accepted and taken back within the same change
+        assert!(breakdowns.iter().all(|b| {
+            b.counts.iter().all(|(outcome, _)| match outcome {
+                Outcome::Retracted(priority) => !b
+                    .counts
+                    .iter()
+                    .any(|(other, _)| *other == Outcome::Accepted(*priority)),
+                _ => true,
+            })
+        }));
+    }
+
+    #[test]
+    fn informational() {
+        let table = PointsTable::default();
+        let mut breakdowns = breakdowns(40, &table, &PriorityWeights::default());
+        let likelihood = breakdowns[0].likelihood;
+        with_informational(&mut breakdowns, 2);
+        assert_eq!(breakdowns[0].to_string(), "Critical, Informational(2)");
+        assert_eq!(breakdowns[0].likelihood, likelihood);
+    }
 
-        assert!(a.unwrap().to_string() == "Critical");
-        assert!(b.unwrap().to_string() == "Critical(2)");
+    #[test]
+    fn greedy_fallback() {
+        let table = PointsTable::default();
+        assert_eq!(most_likely(500).unwrap(), "Critical(12), High");
+        assert_eq!(
+            most_likely(-500).unwrap(),
+            "retracted Critical(12), retracted High"
+        );
+        assert_eq!(
+            greedy(65, &table).unwrap().to_string(),
+            "Critical, High, Low"
+        );
+        assert!(greedy(3, &table).is_none());
     }
 
     #[test]
     fn none() {
-        let a = calculate_points_breakdown(0);
-        let b = calculate_points_breakdown(3);
+        assert!(most_likely(3).is_none());
+        assert!(most_likely(-3).is_none());
+    }
+
+    #[test]
+    fn losses() {
+        let table = PointsTable::default();
+        let weights = PriorityWeights::default();
+
+        assert!(most_likely(0).is_none());
+        assert_eq!(
+            describe_loss(5, &breakdowns(-5, &table, &weights)).unwrap(),
+            "likely -5: N/A penalty"
+        );
+        assert_eq!(most_likely(-40).unwrap(), "retracted Critical");
+        assert_eq!(most_likely(-10).unwrap(), "retracted Medium");
+
+        let table: PointsTable = "na=-1".parse().unwrap();
+        assert_eq!(
+            describe_loss(2, &breakdowns(-2, &table, &weights)).unwrap(),
+            "likely -2: N/A penalty(2)"
+        );
+    }
+
+    #[test]
+    fn ranked_by_recent_disclosures() {
+        let table = PointsTable::default();
+        let uniform = breakdowns(40, &table, &PriorityWeights::default());
+        assert_eq!(describe(&uniform).unwrap(), "Critical");
+
+        let mostly_high = PriorityWeights::from_priorities([Priority::P2; 20]);
+        let breakdowns = breakdowns(40, &table, &mostly_high);
+        assert_eq!(breakdowns[0].to_string(), "High(2)");
+        assert!(
+            describe(&breakdowns)
+                .unwrap()
+                .starts_with("ambiguous: High(2) ")
+        );
+    }
+
+    #[test]
+    fn custom_points_table() {
+        let tables: Vec<ProgramPointsTable> = ["p1=50", "acme:p1=100,P2=50"]
+            .iter()
+            .map(|t| t.parse().unwrap())
+            .collect();
+
+        let acme = PointsTable::for_program(&tables, "acme");
+        let weights = PriorityWeights::default();
+        assert_eq!(breakdowns(100, &acme, &weights)[0].to_string(), "Critical");
 
-        assert!(a.is_none());
-        assert!(b.is_none());
+        let other = PointsTable::for_program(&tables, "other");
+        assert_eq!(breakdowns(50, &other, &weights)[0].to_string(), "Critical");
+        assert!("p5=1".parse::<PointsTable>().is_err());
     }
 }
@@ -1,14 +1,856 @@
-use clap::Parser;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Display},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{Context, ensure};
+use bugcrowd_api::{
+    models::{ActivityKind, EngagementKind},
+    rate_limit::{DEFAULT_MAX_CONCURRENCY, DEFAULT_REQUESTS_PER_SECOND},
+};
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, parser::ValueSource};
+use log::{error, info, warn};
+use serde::{
+    Deserialize, Deserializer, Serialize,
+    de::{self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor},
+};
+use tokio::{
+    signal::unix::{SignalKind, signal},
+    sync::watch,
+};
+
+use crate::{
+    breakdown::{PointsTable, ProgramPointsTable},
+    template::Template,
+    webhook::Webhook,
+};
+
+/// Sink events are posted to unless routed elsewhere
+pub const DEFAULT_SINK: &str = "default";
 
 #[derive(Default, Debug, Parser)]
 #[clap(author = "daniel", version, about)]
 pub struct Arguments {
+    /// TOML or YAML file declaring programs, sinks and routes, reloaded on
+    /// SIGHUP or when it changes. Command line arguments and environment
+    /// variables override its keys
+    #[arg(short = 'c', long = "config", env = "CONFIG_FILE")]
+    pub config: Option<PathBuf>,
     #[arg(short = 'T', long = "session-token", env = "SESSION_TOKEN")]
     pub bugcrowd_session_token: Option<String>,
+    /// File holding the session token, reloaded on SIGHUP or when it changes
+    #[arg(long = "session-token-file", env = "SESSION_TOKEN_FILE")]
+    pub bugcrowd_session_token_file: Option<PathBuf>,
+    /// Sign in with these credentials when there is no session or it expired
+    #[arg(long = "email", env = "BUGCROWD_EMAIL", requires = "bugcrowd_password")]
+    pub bugcrowd_email: Option<String>,
+    #[arg(
+        long = "password",
+        env = "BUGCROWD_PASSWORD",
+        requires = "bugcrowd_email"
+    )]
+    pub bugcrowd_password: Option<String>,
+    /// Base32 secret of the authenticator app, when 2FA is enabled
+    #[arg(long = "totp-secret", env = "BUGCROWD_TOTP_SECRET")]
+    pub bugcrowd_totp_secret: Option<String>,
+    /// Requests per second sent to Bugcrowd, lowered automatically on 429s
+    #[arg(
+        long = "requests-per-second",
+        env = "REQUESTS_PER_SECOND",
+        default_value_t = DEFAULT_REQUESTS_PER_SECOND
+    )]
+    pub requests_per_second: f64,
+    /// Requests to Bugcrowd in flight at once
+    #[arg(
+        long = "max-concurrent-requests",
+        env = "MAX_CONCURRENT_REQUESTS",
+        default_value_t = DEFAULT_MAX_CONCURRENCY
+    )]
+    pub max_concurrent_requests: usize,
     #[arg(short = 'R', long = "redis", env = "REDIS_URL")]
-    pub redis: String,
+    pub redis: Option<String>,
     #[arg(short = 'H', long = "handle", env = "BUGCROWD_ENGAGEMENT")]
-    pub engagement_handle: String,
+    pub engagement_handle: Option<String>,
+    /// Webhook of the default sink
     #[arg(short = 'W', long = "webhook-url", env = "DISCORD_WEBHOOK_URL")]
-    pub discord_webhook_url: String,
+    pub discord_webhook_url: Option<String>,
+    #[arg(long = "track-rank-changes", env = "TRACK_RANK_CHANGES")]
+    pub track_rank_changes: bool,
+    /// Notify about scope, reward and status changes of every program's brief
+    #[arg(long = "track-brief", env = "TRACK_BRIEF")]
+    pub track_brief: bool,
+    /// Crowdstream activity to notify about: accepted, rewarded, disclosed
+    #[arg(
+        long = "crowdstream-activity",
+        env = "CROWDSTREAM_ACTIVITY",
+        value_delimiter = ',',
+        default_value = "disclosed"
+    )]
+    pub crowdstream_activity: Vec<ActivityKind>,
+    /// More newly disclosed reports than this at once are withheld until confirmed
+    #[arg(
+        long = "max-new-disclosures",
+        env = "MAX_NEW_DISCLOSURES",
+        default_value_t = 10
+    )]
+    pub max_new_disclosures: usize,
+    /// Track every engagement visible to the session instead of a single handle
+    #[arg(long = "discover", env = "DISCOVER_ENGAGEMENTS")]
+    pub discover: bool,
+    /// Only discover these engagement types: bug_bounty, vdp
+    #[arg(long = "discover-type", env = "DISCOVER_TYPE", value_delimiter = ',')]
+    pub discover_type: Vec<EngagementKind>,
+    /// Only discover engagements whose handle or name match, `*` is a wildcard
+    #[arg(long = "discover-pattern", env = "DISCOVER_PATTERN")]
+    pub discover_pattern: Option<String>,
+    /// Hall of fame points per priority and N/A penalty, `p1=50,na=1` for every program or
+    /// `acme:p1=50,p2=25` for a single one, separated by `;`
+    #[arg(long = "points-table", env = "POINTS_TABLE", value_delimiter = ';')]
+    pub points_table: Vec<ProgramPointsTable>,
+    /// Researchers to follow across tracked programs
+    #[arg(
+        long = "follow-researcher",
+        env = "FOLLOW_RESEARCHERS",
+        value_delimiter = ','
+    )]
+    pub follow_researchers: Vec<String>,
+
+    /// Webhooks by sink name, always including [`DEFAULT_SINK`]
+    #[arg(skip)]
+    pub sinks: BTreeMap<String, Webhook>,
+    /// Programs tracked without discovery, and settings of discovered ones
+    #[arg(skip)]
+    pub programs: Vec<ProgramConfig>,
+    /// First matching route picks the sink of an event
+    #[arg(skip)]
+    pub routes: Vec<Route>,
+    /// Message templates by source, the embeds are sent as built otherwise
+    #[arg(skip)]
+    pub templates: BTreeMap<Source, Template>,
+}
+
+impl Arguments {
+    pub fn load() -> Result<Self, anyhow::Error> {
+        Self::load_from(&Self::command().get_matches())
+    }
+
+    /// Reads the config file again, with the same command line and environment
+    pub fn reload() -> Result<Self, anyhow::Error> {
+        Self::load_from(&Self::command().try_get_matches()?)
+    }
+
+    fn load_from(matches: &ArgMatches) -> Result<Self, anyhow::Error> {
+        let file = match matches.get_one::<PathBuf>("config") {
+            Some(path) => Some(ConfigFile::read(path)?),
+            None => None,
+        };
+        Self::from_matches(matches, file)
+    }
+
+    fn from_matches(matches: &ArgMatches, file: Option<ConfigFile>) -> Result<Self, anyhow::Error> {
+        let mut arguments = Self::from_arg_matches(matches)?;
+        if let Some(file) = file {
+            let explicit = |id: &str| {
+                matches!(
+                    matches.value_source(id),
+                    Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+                )
+            };
+            arguments.merge(file, explicit);
+        }
+        arguments.resolve()?;
+        Ok(arguments)
+    }
+
+    /// Takes the keys of `file` that weren't given explicitly
+    fn merge(&mut self, file: ConfigFile, explicit: impl Fn(&str) -> bool) {
+        macro_rules! merge {
+            ($($key:ident => $field:ident),* $(,)?) => {$(
+                if let Some(value) = file.$key.filter(|_| !explicit(stringify!($field))) {
+                    self.$field = value.into();
+                }
+            )*};
+        }
+
+        merge!(
+            session_token => bugcrowd_session_token,
+            session_token_file => bugcrowd_session_token_file,
+            email => bugcrowd_email,
+            password => bugcrowd_password,
+            totp_secret => bugcrowd_totp_secret,
+            requests_per_second => requests_per_second,
+            max_concurrent_requests => max_concurrent_requests,
+            redis => redis,
+            track_rank_changes => track_rank_changes,
+            track_brief => track_brief,
+            crowdstream_activity => crowdstream_activity,
+            max_new_disclosures => max_new_disclosures,
+            discover => discover,
+            discover_type => discover_type,
+            discover_pattern => discover_pattern,
+            follow_researchers => follow_researchers,
+        );
+        if let Some(table) = file.points_table.filter(|_| !explicit("points_table")) {
+            self.points_table = vec![ProgramPointsTable {
+                program: None,
+                table,
+            }];
+        }
+
+        self.sinks = file
+            .sinks
+            .into_iter()
+            .map(|(name, sink)| (name, sink.discord_webhook_url))
+            .collect();
+        self.programs = file.programs;
+        self.routes = file.routes;
+        self.templates = file.templates;
+    }
+
+    /// Checks what can only be checked once every source is merged
+    fn resolve(&mut self) -> Result<(), anyhow::Error> {
+        ensure!(
+            self.redis.is_some(),
+            "no Redis URL, set REDIS_URL or `redis` in the config file"
+        );
+        ensure!(
+            self.requests_per_second.is_finite() && self.requests_per_second > 0.0,
+            "requests_per_second must be positive, got {}",
+            self.requests_per_second
+        );
+        ensure!(
+            self.max_concurrent_requests > 0,
+            "max_concurrent_requests must be at least 1"
+        );
+
+        if let Some(url) = &self.discord_webhook_url {
+            let webhook = Webhook::from_url(url).context("invalid Discord webhook URL")?;
+            self.sinks.insert(DEFAULT_SINK.to_string(), webhook);
+        }
+        ensure!(
+            self.sinks.contains_key(DEFAULT_SINK),
+            "no Discord webhook, set DISCORD_WEBHOOK_URL or `sinks.{DEFAULT_SINK}` in the config file"
+        );
+
+        let declared = |handle: &&String| self.programs.iter().any(|p| &p.handle == *handle);
+        if let Some(handle) = self.engagement_handle.as_ref().filter(|h| !declared(h)) {
+            self.programs.push(ProgramConfig::new(handle));
+        }
+        ensure!(
+            self.discover || !self.programs.is_empty(),
+            "no program to track, set BUGCROWD_ENGAGEMENT, `programs` in the config file or enable discovery"
+        );
+        Ok(())
+    }
+
+    /// Settings of `handle`, the defaults when the config file doesn't declare it
+    pub fn program(&self, handle: &str) -> ProgramConfig {
+        self.programs
+            .iter()
+            .find(|program| program.handle == handle)
+            .cloned()
+            .unwrap_or_else(|| ProgramConfig::new(handle))
+    }
+
+    /// Points table of `handle`, a table given for it on the command line wins
+    /// over the one of its settings
+    pub fn points_table(&self, handle: &str) -> PointsTable {
+        let explicit = self
+            .points_table
+            .iter()
+            .any(|table| table.program.as_deref() == Some(handle));
+        match self.program(handle).points_table {
+            Some(table) if !explicit => table,
+            _ => PointsTable::for_program(&self.points_table, handle),
+        }
+    }
+
+    /// Webhook of the first matching route, then of the program's sink
+    pub fn webhook(&self, source: Source, program: Option<&str>) -> Webhook {
+        let route = self
+            .routes
+            .iter()
+            .find(|route| route.matches(source, program))
+            .map(|route| route.sink.clone());
+        let sink = route
+            .or_else(|| program.and_then(|handle| self.program(handle).sink))
+            .unwrap_or_else(|| DEFAULT_SINK.to_string());
+        self.sinks[&sink].clone()
+    }
+}
+
+/// Where an event comes from, for routing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Source {
+    HallOfFame,
+    DisclosedReports,
+    Crowdstream,
+    Announcements,
+    Brief,
+    Engagements,
+    Researchers,
+    Session,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Route {
+    /// Every source when unset
+    pub source: Option<Source>,
+    /// Every program when unset, events not tied to a program only match then
+    pub program: Option<String>,
+    pub sink: String,
+}
+
+impl Route {
+    fn matches(&self, source: Source, program: Option<&str>) -> bool {
+        self.source.is_none_or(|s| s == source)
+            && self.program.as_deref().is_none_or(|p| Some(p) == program)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProgramConfig {
+    pub handle: String,
+    #[serde(default)]
+    pub intervals: Intervals,
+    /// [`DEFAULT_SINK`] when unset
+    pub sink: Option<String>,
+    #[serde(default, deserialize_with = "parse")]
+    pub points_table: Option<PointsTable>,
+    /// Poll the engagement brief, also enabled for every program by `--track-brief`
+    #[serde(default)]
+    pub track_brief: bool,
+}
+
+impl ProgramConfig {
+    fn new(handle: &str) -> Self {
+        Self {
+            handle: handle.to_string(),
+            intervals: Intervals::default(),
+            sink: None,
+            points_table: None,
+            track_brief: false,
+        }
+    }
+}
+
+/// Time between polls, given in seconds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Intervals {
+    #[serde(deserialize_with = "seconds")]
+    pub hall_of_fame: Duration,
+    #[serde(deserialize_with = "seconds")]
+    pub disclosed_reports: Duration,
+    #[serde(deserialize_with = "seconds")]
+    pub crowdstream: Duration,
+    #[serde(deserialize_with = "seconds")]
+    pub announcements: Duration,
+    #[serde(deserialize_with = "seconds")]
+    pub brief: Duration,
+}
+
+impl Default for Intervals {
+    fn default() -> Self {
+        Self {
+            hall_of_fame: Duration::from_secs(60 * 3),
+            disclosed_reports: Duration::from_secs(60 * 5),
+            crowdstream: Duration::from_secs(60 * 5),
+            announcements: Duration::from_secs(60 * 2),
+            brief: Duration::from_secs(60 * 10),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Sink {
+    #[serde(deserialize_with = "webhook_url")]
+    discord_webhook_url: Webhook,
+}
+
+/// Contents of `--config`, keys are named after the long arguments
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    session_token: Option<String>,
+    session_token_file: Option<PathBuf>,
+    email: Option<String>,
+    password: Option<String>,
+    totp_secret: Option<String>,
+    requests_per_second: Option<f64>,
+    max_concurrent_requests: Option<usize>,
+    redis: Option<String>,
+    track_rank_changes: Option<bool>,
+    track_brief: Option<bool>,
+    crowdstream_activity: Option<Vec<ActivityKind>>,
+    max_new_disclosures: Option<usize>,
+    discover: Option<bool>,
+    discover_type: Option<Vec<EngagementKind>>,
+    discover_pattern: Option<String>,
+    /// Table of programs without their own
+    #[serde(default, deserialize_with = "parse")]
+    points_table: Option<PointsTable>,
+    follow_researchers: Option<Vec<String>>,
+    #[serde(default)]
+    sinks: BTreeMap<String, Sink>,
+    #[serde(default)]
+    programs: Vec<ProgramConfig>,
+    #[serde(default)]
+    routes: Vec<Route>,
+    #[serde(default)]
+    templates: BTreeMap<Source, Template>,
+}
+
+impl ConfigFile {
+    /// YAML for `.yaml` and `.yml` files, TOML otherwise
+    pub fn read(path: &Path) -> Result<Self, anyhow::Error> {
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let yaml = path
+            .extension()
+            .is_some_and(|extension| extension == "yaml" || extension == "yml");
+        Self::parse(&source, yaml)
+            .with_context(|| format!("invalid config file {}", path.display()))
+    }
+
+    fn parse(source: &str, yaml: bool) -> Result<Self, anyhow::Error> {
+        let file: Self = if yaml {
+            serde_yaml::from_str(source)?
+        } else {
+            toml::from_str(source)?
+        };
+
+        // Sinks are only known once parsed, the second pass reports the line
+        // of a bad reference the way the formats report syntax errors
+        let references = References { sinks: &file.sinks };
+        if yaml {
+            references.deserialize(serde_yaml::Deserializer::from_str(source))?;
+        } else {
+            references.deserialize(toml::Deserializer::new(source))?;
+        }
+        Ok(file)
+    }
+}
+
+/// Checks the sinks and handles of programs and routes. Errors are raised
+/// while deserializing the offending value, so they carry its location
+struct References<'a> {
+    sinks: &'a BTreeMap<String, Sink>,
+}
+
+impl<'de> DeserializeSeed<'de> for References<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for References<'_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a config file")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let mut handles = BTreeSet::new();
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "programs" | "routes" => map.next_value_seed(Entries {
+                    sinks: self.sinks,
+                    handles: &mut handles,
+                })?,
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `[[programs]]` or `[[routes]]`
+struct Entries<'a> {
+    sinks: &'a BTreeMap<String, Sink>,
+    handles: &'a mut BTreeSet<String>,
+}
+
+impl<'de> DeserializeSeed<'de> for Entries<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for Entries<'_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a list of tables")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        let Self { sinks, handles } = self;
+        while seq
+            .next_element_seed(Entry {
+                sinks,
+                handles: &mut *handles,
+            })?
+            .is_some()
+        {}
+        Ok(())
+    }
+}
+
+struct Entry<'a> {
+    sinks: &'a BTreeMap<String, Sink>,
+    handles: &'a mut BTreeSet<String>,
+}
+
+impl<'de> DeserializeSeed<'de> for Entry<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for Entry<'_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a table")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "sink" => map.next_value_seed(Reference::Sink(self.sinks))?,
+                "handle" => map.next_value_seed(Reference::Handle(&mut *self.handles))?,
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+enum Reference<'a> {
+    /// Must be declared, the default sink can also be given on the command line
+    Sink(&'a BTreeMap<String, Sink>),
+    /// Handles seen so far, a program must only be declared once
+    Handle(&'a mut BTreeSet<String>),
+}
+
+impl<'de> DeserializeSeed<'de> for Reference<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_str(self)
+    }
+}
+
+impl<'de> Visitor<'de> for Reference<'_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a string")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<(), E> {
+        match self {
+            Self::Sink(sinks) => {
+                if value != DEFAULT_SINK && !sinks.contains_key(value) {
+                    return Err(E::custom(format!("unknown sink {value:?}")));
+                }
+            }
+            Self::Handle(handles) => {
+                if !handles.insert(value.to_string()) {
+                    return Err(E::custom(format!("program {value:?} is declared twice")));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    let text = String::deserialize(deserializer)?;
+    text.parse().map(Some).map_err(de::Error::custom)
+}
+
+fn seconds<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    match u64::deserialize(deserializer)? {
+        0 => Err(de::Error::custom("interval must be at least a second")),
+        seconds => Ok(Duration::from_secs(seconds)),
+    }
+}
+
+fn webhook_url<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Webhook, D::Error> {
+    let url = String::deserialize(deserializer)?;
+    Webhook::from_url(&url).ok_or_else(|| de::Error::custom("invalid Discord webhook URL"))
+}
+
+/// Reloads the config file on SIGHUP or when it changes. Invalid configs are
+/// logged and the running one is kept
+pub struct Reloader {
+    pub file: PathBuf,
+    pub config: watch::Sender<Arc<Arguments>>,
+}
+
+impl Reloader {
+    const CHECK_FILE_INTERVAL: Duration = Duration::from_secs(30);
+
+    pub async fn run(&mut self) -> Result<(), anyhow::Error> {
+        let mut hangup = signal(SignalKind::hangup())?;
+        let mut check_file = tokio::time::interval(Self::CHECK_FILE_INTERVAL);
+        let mut modified = self.file_modified().await;
+
+        loop {
+            tokio::select! {
+                _ = hangup.recv() => {
+                    info!("received SIGHUP, reloading config");
+                    self.reload();
+                }
+
+                _ = check_file.tick() => {
+                    let file_modified = self.file_modified().await;
+                    if file_modified != modified {
+                        info!("config file changed, reloading");
+                        modified = file_modified;
+                        self.reload();
+                    }
+                }
+            }
+        }
+    }
+
+    fn reload(&self) {
+        let config = match Arguments::reload() {
+            Ok(config) => config,
+            Err(error) => {
+                error!("keeping the running config, reload failed: {error:#}");
+                return;
+            }
+        };
+
+        let ignored = restart_required(&self.config.borrow(), &config);
+        if !ignored.is_empty() {
+            warn!("restart to apply changes to {}", ignored.join(", "));
+        }
+        self.config.send_replace(Arc::new(config));
+        info!("config reloaded");
+    }
+
+    async fn file_modified(&self) -> Option<SystemTime> {
+        let metadata = tokio::fs::metadata(&self.file).await.ok()?;
+        metadata.modified().ok()
+    }
+}
+
+/// Keys of `new` only read at startup that differ from `old`
+fn restart_required(old: &Arguments, new: &Arguments) -> Vec<&'static str> {
+    let mut changed = vec![];
+    macro_rules! compare {
+        ($($key:literal => $field:ident),* $(,)?) => {$(
+            if old.$field != new.$field {
+                changed.push($key);
+            }
+        )*};
+    }
+
+    compare!(
+        "session_token" => bugcrowd_session_token,
+        "session_token_file" => bugcrowd_session_token_file,
+        "email" => bugcrowd_email,
+        "password" => bugcrowd_password,
+        "totp_secret" => bugcrowd_totp_secret,
+        "requests_per_second" => requests_per_second,
+        "max_concurrent_requests" => max_concurrent_requests,
+        "redis" => redis,
+    );
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOML: &str = r#"
+redis = "redis://localhost"
+max_new_disclosures = 20
+track_rank_changes = true
+
+[sinks.default]
+discord_webhook_url = "https://discord.com/api/webhooks/1/default"
+
+[sinks.alerts]
+discord_webhook_url = "https://discord.com/api/webhooks/2/alerts"
+
+[[programs]]
+handle = "acme"
+points_table = "p1=100"
+intervals = { hall_of_fame = 60 }
+
+[[programs]]
+handle = "umbrella"
+sink = "alerts"
+
+[[routes]]
+source = "disclosed_reports"
+program = "acme"
+sink = "alerts"
+
+[templates.disclosed_reports]
+content = "<@&42> {fields.Severity} disclosed on {program}"
+"#;
+
+    fn arguments(args: &[&str], file: ConfigFile) -> Result<Arguments, anyhow::Error> {
+        let args = ["bugcrowd_tracker"].iter().chain(args);
+        let matches = Arguments::command().try_get_matches_from(args)?;
+        Arguments::from_matches(&matches, Some(file))
+    }
+
+    #[test]
+    fn command_line_overrides_the_file() {
+        let file = ConfigFile::parse(TOML, false).unwrap();
+        let config = arguments(&["--max-new-disclosures", "3"], file).unwrap();
+
+        assert_eq!(config.max_new_disclosures, 3);
+        assert!(config.track_rank_changes);
+        assert_eq!(config.redis.as_deref(), Some("redis://localhost"));
+
+        let acme = config.program("acme");
+        assert_eq!(acme.intervals.hall_of_fame, Duration::from_secs(60));
+        assert_eq!(acme.intervals.brief, Intervals::default().brief);
+        assert_eq!(config.points_table("acme"), "p1=100".parse().unwrap());
+        assert_eq!(config.points_table("umbrella"), PointsTable::default());
+    }
+
+    #[test]
+    fn routes() {
+        let file = ConfigFile::parse(TOML, false).unwrap();
+        let config = arguments(&[], file).unwrap();
+        let sink = |source, program| config.webhook(source, program).token;
+
+        assert_eq!(sink(Source::DisclosedReports, Some("acme")), "alerts");
+        assert_eq!(sink(Source::HallOfFame, Some("acme")), "default");
+        assert_eq!(sink(Source::HallOfFame, Some("umbrella")), "alerts");
+        assert_eq!(sink(Source::Session, None), "default");
+    }
+
+    #[test]
+    fn templates() {
+        let file = ConfigFile::parse(TOML, false).unwrap();
+        let config = arguments(&[], file).unwrap();
+        assert!(config.templates.contains_key(&Source::DisclosedReports));
+        assert!(!config.templates.contains_key(&Source::HallOfFame));
+
+        let unclosed = TOML.replace("{program}", "{program");
+        let error = format!("{:#}", ConfigFile::parse(&unclosed, false).unwrap_err());
+        assert!(error.contains("unclosed placeholder"), "{error}");
+        assert!(error.contains("line 27"), "{error}");
+    }
+
+    #[test]
+    fn yaml() {
+        let source = "
+redis: redis://localhost
+sinks:
+  default:
+    discord_webhook_url: https://discord.com/api/webhooks/1/default
+programs:
+  - handle: acme
+    intervals:
+      announcements: 30
+";
+        let file = ConfigFile::parse(source, true).unwrap();
+        let config = arguments(&[], file).unwrap();
+        assert_eq!(
+            config.program("acme").intervals.announcements,
+            Duration::from_secs(30)
+        );
+    }
+
+    #[test]
+    fn settings_only_read_at_startup() {
+        let config = |source: &str| arguments(&[], ConfigFile::parse(source, false).unwrap());
+        let old = config(TOML).unwrap();
+        let new = config(&TOML.replace("redis://localhost", "redis://redis")).unwrap();
+        assert_eq!(restart_required(&old, &new), ["redis"]);
+        assert!(restart_required(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_rate_limits() {
+        let file = || ConfigFile::parse(TOML, false).unwrap();
+        let error = arguments(&["--requests-per-second", "0"], file()).unwrap_err();
+        assert!(error.to_string().contains("requests_per_second"), "{error}");
+
+        let file = ConfigFile::parse(&format!("max_concurrent_requests = 0\n{TOML}"), false);
+        let error = arguments(&[], file.unwrap()).unwrap_err();
+        assert!(
+            error.to_string().contains("max_concurrent_requests"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn example() {
+        let example = include_str!("../../config.example.toml").replace("<id>/<token>", "1/token");
+        let file = ConfigFile::parse(&example, false).unwrap();
+        assert_eq!(file.programs[0].handle, "acme");
+    }
+
+    #[test]
+    fn errors_have_line_numbers() {
+        let error = |source: &str, yaml| {
+            let error = ConfigFile::parse(source, yaml).unwrap_err();
+            format!("{error:#}")
+        };
+
+        let invalid_url = TOML.replace("webhooks/2/alerts", "alerts");
+        let message = error(&invalid_url, false);
+        assert!(message.contains("line 10"), "{message}");
+        assert!(message.contains("invalid Discord webhook URL"), "{message}");
+
+        let zero_interval = TOML.replace("hall_of_fame = 60", "hall_of_fame = 0");
+        assert!(error(&zero_interval, false).contains("line 15"));
+
+        // The first of the two references to the sink
+        let unknown_sink = TOML.replace("sink = \"alerts\"", "sink = \"pager\"");
+        let message = error(&unknown_sink, false);
+        assert!(message.contains("line 19"), "{message}");
+        assert!(message.contains("unknown sink \"pager\""), "{message}");
+
+        // Found by position, not by searching the text for the value
+        let duplicate = TOML.replace("handle = \"umbrella\"", "handle = \"acme\"");
+        let duplicate = format!("{duplicate}# handle = \"acme\" only once\n");
+        let message = error(&duplicate, false);
+        assert!(message.contains("line 18"), "{message}");
+        assert!(message.contains("program \"acme\" is declared twice"));
+
+        let message = error("programs:\n  - handle: acme\n    sink: pager\n", true);
+        assert!(message.contains("line 3"), "{message}");
+        assert!(message.contains("unknown sink \"pager\""), "{message}");
+
+        let message = error("redis: redis://localhost\nprograms: 5\n", true);
+        assert!(message.contains("line 2"), "{message}");
+    }
 }
@@ -1,21 +1,22 @@
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
-use bugcrowd_api::client::BugcrowdApi;
-use clap::Parser;
-use log::{error, info};
+use bugcrowd_api::{auth::Credentials, client::BugcrowdApi};
+use log::{error, info, warn};
+use tokio::{sync::watch, task::JoinHandle, time::Instant};
 use tracing_subscriber::{EnvFilter, filter::LevelFilter};
-use twilight_model::id::Id;
 
 use crate::{
-    config::Arguments,
-    store::{DisclosedReportsStore, HallOfFameStore},
-    webhook::Webhook,
+    config::{Arguments, Source},
+    program::{Context, Programs},
+    store::{EngagementsStore, SessionStore},
 };
 
 mod breakdown;
 mod config;
 mod poll;
+mod program;
 mod store;
+mod template;
 mod webhook;
 
 #[tokio::main]
@@ -25,98 +26,262 @@ async fn main() {
         .from_env_lossy();
     tracing_subscriber::fmt().with_env_filter(env_filter).init();
 
-    let config = Arguments::parse();
-    let bugcrowd_api = BugcrowdApi::new(config.bugcrowd_session_token);
-    let program_handle = config.engagement_handle;
-    let webhook =
-        extract_webhook(&config.discord_webhook_url).expect("Invalid Discord webhook URL");
+    let config = match Arguments::load() {
+        Ok(config) => config,
+        Err(error) => {
+            error!("invalid configuration: {error:#}");
+            std::process::exit(2);
+        }
+    };
 
-    let redis = redis::Client::open(config.redis).expect("Invalid Redis connection URI");
+    let redis_url = config.redis.as_deref().unwrap();
+    let redis = redis::Client::open(redis_url).expect("Invalid Redis connection URI");
     let redis = redis
         .get_connection_manager()
         .await
         .expect("Failed to open Redis connection");
 
-    let program_access = bugcrowd_api
-        .has_program_access(&program_handle)
-        .await
-        .unwrap();
-    if !program_access {
-        error!(
-            "Unable to fetch program, ensure you're using the program handle, and a session token for private programs"
-        );
-        return;
+    let mut session_store = SessionStore {
+        redis_connection: redis.clone(),
+    };
+    let session_token = session_token(&config, &mut session_store).await;
+    let credentials = match (&config.bugcrowd_email, &config.bugcrowd_password) {
+        (Some(email), Some(password)) => Some(Credentials {
+            email: email.clone(),
+            password: password.clone(),
+            totp_secret: config.bugcrowd_totp_secret.clone(),
+        }),
+        _ => None,
+    };
+    let authenticated = session_token.is_some() || credentials.is_some();
+    let bugcrowd_api = BugcrowdApi::builder()
+        .session_token(session_token.clone())
+        .credentials(credentials.clone())
+        .rate_limit(config.requests_per_second, config.max_concurrent_requests)
+        .expect("Invalid rate limit")
+        .build();
+
+    if session_token.is_none() && credentials.is_some() {
+        bugcrowd_api
+            .login()
+            .await
+            .expect("Failed to sign in to Bugcrowd");
     }
 
-    let mut polls = vec![];
+    let (config_sender, config_receiver) = watch::channel(Arc::new(config));
+    let context = Context {
+        bugcrowd: bugcrowd_api,
+        redis,
+        config: config_receiver,
+    };
+    let config = context.config.borrow().clone();
 
-    // ==== Hall Of Fame ====
-    {
-        let store = HallOfFameStore {
-            program_handle: program_handle.clone(),
-            redis_connection: redis.clone(),
-        };
-        let channel = webhook::hall_of_fame::background_channel(webhook.clone());
-        let mut poller = poll::hall_of_fame::Poller {
-            bugcrowd: bugcrowd_api.clone(),
-            store,
-            program_handle: program_handle.clone(),
-            channel,
+    if let Some(file) = config.config.clone() {
+        let mut reloader = config::Reloader {
+            file,
+            config: config_sender,
         };
+        tokio::task::spawn(async move {
+            if let Err(error) = reloader.run().await {
+                error!("config reloader stopped: {error:#?}");
+            }
+        });
+    }
 
-        const POLL_INTERVAL: Duration = Duration::from_secs(60 * 3);
-        polls.push(tokio::task::spawn(async move {
-            info!("started polling hall of fame");
+    if authenticated {
+        watch_session(&context);
+    }
 
-            loop {
-                poller.poll().await.expect("hall of fame poll failed");
-                tokio::time::sleep(POLL_INTERVAL).await;
-            }
-        }));
+    let mut token_sync = poll::session::TokenSync {
+        bugcrowd: context.bugcrowd.clone(),
+        store: session_store,
+        file: config.bugcrowd_session_token_file.clone(),
+        configured_token: config.bugcrowd_session_token.clone(),
+    };
+    tokio::task::spawn(async move {
+        if let Err(error) = token_sync.run().await {
+            error!("session token sync stopped: {error:#?}");
+        }
+    });
+
+    let (tracked_sender, tracked) = watch::channel(vec![]);
+    let researchers = follow_researchers(&context, tracked);
+    tokio::select! {
+        _ = track(context, tracked_sender) => {}
+        _ = researchers => {}
     }
+}
 
-    // ==== Crowdstream Disclosed Reports ====
-    {
-        let store = DisclosedReportsStore {
-            program_handle: program_handle.clone(),
-            redis_connection: redis.clone(),
-        };
-        let channel = webhook::disclosed_reports::background_channel(webhook.clone());
-        let mut poller = poll::disclosed_reports::Poller {
-            bugcrowd: bugcrowd_api.clone(),
-            store,
-            program_handle: program_handle.clone(),
-            channel,
-        };
+/// Token file first, then the latest refresh of the configured token, then
+/// the configured token itself
+async fn session_token(config: &Arguments, store: &mut SessionStore) -> Option<String> {
+    if let Some(file) = &config.bugcrowd_session_token_file {
+        match tokio::fs::read_to_string(file).await {
+            Ok(token) if !token.trim().is_empty() => return Some(token.trim().to_string()),
+            Ok(_) => warn!("session token file {} is empty", file.display()),
+            Err(error) => warn!("failed to read {}: {error:#?}", file.display()),
+        }
+    }
+
+    let configured_token = config.bugcrowd_session_token.as_ref()?;
+    match store.refreshed_token(configured_token).await {
+        Ok(Some(token)) => {
+            info!("using refreshed session token");
+            Some(token)
+        }
+        Ok(None) => Some(configured_token.clone()),
+        Err(error) => {
+            warn!("failed to load refreshed session token: {error:#?}");
+            Some(configured_token.clone())
+        }
+    }
+}
+
+fn watch_session(context: &Context) {
+    let channel = webhook::session::background_channel(context.sink(Source::Session, None));
+    let mut watcher = poll::session::Watcher {
+        state: context.bugcrowd.session_state(),
+        channel,
+    };
 
-        const POLL_INTERVAL: Duration = Duration::from_secs(60 * 5);
-        polls.push(tokio::task::spawn(async move {
-            info!("started polling Crowdstream disclosed reports");
+    tokio::task::spawn(async move {
+        if let Err(error) = watcher.watch().await {
+            error!("session watcher stopped: {error:#?}");
+        }
+    });
+}
+
+fn follow_researchers(context: &Context, programs: watch::Receiver<Vec<String>>) -> JoinHandle<()> {
+    let channel = webhook::researchers::background_channel(context.sink(Source::Researchers, None));
+    let mut poller = poll::researchers::Poller {
+        bugcrowd: context.bugcrowd.clone(),
+        redis_connection: context.redis.clone(),
+        channel,
+        usernames: vec![],
+        programs,
+    };
+
+    const POLL_INTERVAL: Duration = Duration::from_secs(60 * 10);
+    let config = context.config.clone();
+    tokio::task::spawn(async move {
+        info!("started polling researchers");
 
-            loop {
-                poller
-                    .poll()
-                    .await
-                    .expect("Crowdstream disclosed reports poll failed");
-                tokio::time::sleep(POLL_INTERVAL).await;
+        loop {
+            poller.usernames = config.borrow().follow_researchers.clone();
+            if let Err(error) = poller.poll().await {
+                error!("researchers poll failed: {error:#}");
             }
-        }));
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    })
+}
+
+/// Tracks the configured programs, or every engagement visible to the session
+/// when discovering, starting and stopping pollers as engagements appear and
+/// disappear or the config is reloaded. Handles of the tracked programs are
+/// published to `tracked`
+async fn track(context: Context, tracked: watch::Sender<Vec<String>>) {
+    const DISCOVER_INTERVAL: Duration = Duration::from_secs(60 * 15);
+    /// Previously discovered programs are kept tracked until then
+    const DISCOVERY_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+    /// Programs whose access couldn't be checked are left out until then
+    const ACCESS_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+    let channel = webhook::engagements::background_channel(context.sink(Source::Engagements, None));
+    let mut poller = poll::engagements::Poller {
+        bugcrowd: context.bugcrowd.clone(),
+        store: EngagementsStore {
+            redis_connection: context.redis.clone(),
+        },
+        channel,
+        kinds: vec![],
+        pattern: None,
+    };
+
+    let mut config = context.config.clone();
+    let initial = config.borrow().clone();
+    if initial.discover && initial.engagement_handle.is_some() {
+        warn!("discovery is enabled, ignoring the engagement handle");
     }
 
-    let _ = futures::future::select_all(polls).await;
+    let mut programs = Programs::default();
+    let mut discovered = vec![];
+    let mut next_discovery = Instant::now();
+
+    loop {
+        let current = config.borrow_and_update().clone();
+        let mut retry_access = None;
+        let handles = if current.discover {
+            if Instant::now() >= next_discovery {
+                info!("discovering engagements");
+                poller.kinds = current.discover_type.clone();
+                poller.pattern = current.discover_pattern.clone();
+                match poller.poll().await {
+                    Ok(engagements) => {
+                        discovered = engagements
+                            .iter()
+                            .map(|engagement| engagement.handle().to_string())
+                            .collect();
+                        next_discovery = Instant::now() + DISCOVER_INTERVAL;
+                    }
+                    Err(error) => {
+                        warn!(
+                            "engagement discovery failed, retrying in {DISCOVERY_RETRY_INTERVAL:?}: {error:#}"
+                        );
+                        next_discovery = Instant::now() + DISCOVERY_RETRY_INTERVAL;
+                    }
+                }
+            }
+            discovered.clone()
+        } else {
+            let mut handles = vec![];
+            for program in &current.programs {
+                if programs.contains(&program.handle) {
+                    handles.push(program.handle.clone());
+                    continue;
+                }
+
+                match has_program_access(&context, &program.handle).await {
+                    Ok(true) => handles.push(program.handle.clone()),
+                    Ok(false) => {}
+                    Err(error) => {
+                        warn!(
+                            "failed to check access to {}, retrying in {ACCESS_RETRY_INTERVAL:?}: {error:#}",
+                            program.handle
+                        );
+                        retry_access = Some(Instant::now() + ACCESS_RETRY_INTERVAL);
+                    }
+                }
+            }
+            handles
+        };
+        programs.sync(&context, &current, &handles);
+        tracked.send_replace(handles);
+
+        loop {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            if let Some(program) = programs.finished() {
+                panic!("pollers for {} stopped", program.program_handle);
+            }
+            if config.has_changed().unwrap_or(false)
+                || (current.discover && Instant::now() >= next_discovery)
+                || retry_access.is_some_and(|retry| Instant::now() >= retry)
+            {
+                break;
+            }
+        }
+    }
 }
 
-fn extract_webhook(url: &str) -> Option<Webhook> {
-    let path_parts: Vec<&str> = url.trim_start_matches("https://").split('/').collect();
-    if path_parts.len() >= 4 && path_parts[1] == "api" && path_parts[2] == "webhooks" {
-        let webhook_id = path_parts[3].parse::<u64>().ok()?;
-        let token = path_parts[4];
-
-        Some(Webhook {
-            id: Id::new(webhook_id),
-            token: token.to_string(),
-        })
-    } else {
-        None
+async fn has_program_access(
+    context: &Context,
+    program_handle: &str,
+) -> Result<bool, anyhow::Error> {
+    let program_access = context.bugcrowd.has_program_access(program_handle).await?;
+    if !program_access {
+        error!(
+            "Unable to fetch program {program_handle}, ensure you're using the program handle, and a session token for private programs"
+        );
     }
+    Ok(program_access)
 }
@@ -1,44 +1,127 @@
 pub mod hall_of_fame {
+    use std::time::Duration;
+
     use bugcrowd_api::{
+        cache::{Polled, Revision},
         client::BugcrowdApi,
-        models::{ANONYMOUS_HERO_NAME, Hero},
+        models::{ANONYMOUS_HERO_NAME, DisclosedReport, Hero, Priority},
     };
-    use log::{debug, trace};
+    use chrono::{DateTime, TimeDelta, Utc};
+    use futures::{StreamExt, TryStreamExt};
+    use log::{debug, info, trace, warn};
     use tokio::sync::mpsc::Sender;
 
-    use crate::store::HallOfFameStore;
+    use crate::{
+        breakdown::{self, PointsBreakdown, PointsTable, PriorityWeights},
+        store::HallOfFameStore,
+    };
 
     pub struct Poller {
         pub bugcrowd: BugcrowdApi,
         pub store: HallOfFameStore,
         pub program_handle: String,
         pub channel: Sender<Event>,
+        pub track_rank_changes: bool,
+        /// First hall of fame page as of the last poll
+        pub revision: Option<Revision>,
+        pub points_table: PointsTable,
+        /// Points that changed since were awarded after it
+        pub last_poll: Option<DateTime<Utc>>,
+        /// Between hall of fame polls
+        pub interval: Duration,
+        /// Between disclosed reports polls, a disclosure noticed up to this
+        /// long before the points changed is linked to them
+        pub disclosures_interval: Duration,
+    }
+
+    /// Recent disclosures used to weigh points breakdowns
+    const RECENT_DISCLOSURES: usize = 25;
+
+    /// What a hero's points change most likely came from
+    #[derive(Debug, Default)]
+    pub struct Explanation {
+        /// Most likely first
+        pub breakdowns: Vec<PointsBreakdown>,
+        /// Reports of the hero disclosed around the time of a gain
+        pub reports: Vec<DisclosedReport>,
+    }
+
+    /// How a points change is broken down
+    enum Strategy {
+        /// Every breakdown within the submission bound, most likely first
+        Enumerate,
+        /// Largest outcomes first, for points of too many submissions
+        Greedy,
     }
 
+    /// Upper bound of [`Event::RankChanged`] sent per poll, so that a single
+    /// big climb doesn't produce one event for every hero it passed.
+    const MAX_RANK_CHANGES_PER_POLL: usize = 3;
+
     #[derive(Debug)]
     pub enum Event {
-        HeroAdded(Hero),
-        HeroUpdated(Hero, Hero),
+        HeroAdded(Hero, Explanation),
+        HeroUpdated(Hero, Hero, Explanation),
         HeroRemoved(Hero),
+        HeroRenamed(Hero, Hero),
+        /// Rank moved without the hero's points changing (old, new, heros that passed them)
+        RankChanged(Hero, Hero, Vec<Hero>),
+        /// Hero climbed the leaderboard of a program that hides points
+        HeroMoved(Hero, Hero),
     }
 
     impl Poller {
         pub async fn poll(&mut self) -> Result<(), anyhow::Error> {
             debug!("polling hall of fame");
 
-            let hall_of_fame = self.bugcrowd.hall_of_fame(&self.program_handle).await?;
-            let hall_of_fame: Vec<Hero> = hall_of_fame.into_iter().collect();
+            let now = Utc::now();
+            let since = self.last_poll.unwrap_or(now - time_delta(self.interval))
+                - time_delta(self.disclosures_interval);
+
+            let polled = self
+                .bugcrowd
+                .hall_of_fame_since(&self.program_handle, self.revision)
+                .await?;
+            let (hall_of_fame, revision) = match polled {
+                Polled::Changed(hall_of_fame, revision) => (hall_of_fame, revision),
+                Polled::Unchanged => {
+                    debug!("hall of fame unchanged");
+                    self.last_poll = Some(now);
+                    return Ok(());
+                }
+            };
             trace!("got heros: {hall_of_fame:?}");
 
             let updated_hof = hall_of_fame.clone();
             let mut saved_hof = self.store.heros().await?;
+            let previous_hof = saved_hof.clone();
+
+            let points_hidden = hall_of_fame.iter().any(|h| h.points_hidden);
+            if !saved_hof.is_empty() && saved_hof.iter().any(|h| h.points_hidden) != points_hidden {
+                info!(
+                    "hall of fame points_hidden changed to {points_hidden}, resetting saved heros"
+                );
+                self.store.save_heros(hall_of_fame).await?;
+                self.revision = Some(revision);
+                self.last_poll = Some(now);
+                return Ok(());
+            }
+
+            let climbers = if points_hidden {
+                climbers(&saved_hof, &hall_of_fame)
+            } else {
+                vec![]
+            };
+            let mut added_heros = vec![];
+            let mut moved_heros = vec![];
+            let mut recent = None;
 
             for hero in updated_hof {
                 let anonymous = hero.username == ANONYMOUS_HERO_NAME;
                 let old_hero = saved_hof.iter().position(|h| h.username == hero.username);
 
                 if old_hero.is_none() {
-                    self.channel.send(Event::HeroAdded(hero)).await?;
+                    added_heros.push(hero);
                     continue;
                 }
 
@@ -46,84 +129,1172 @@ pub mod hall_of_fame {
                 let old_hero = saved_hof.remove(old_hero);
                 if hero.points != old_hero.points && !anonymous {
                     debug!("updated {old_hero:?} -> {hero:?}");
+                    let points = hero.points as i32 - old_hero.points as i32;
+                    let explanation = self
+                        .explain(&hero, points, Strategy::Enumerate, since, &mut recent)
+                        .await;
                     self.channel
-                        .send(Event::HeroUpdated(old_hero, hero))
+                        .send(Event::HeroUpdated(old_hero, hero, explanation))
                         .await?;
+                } else if climbers.contains(&hero.username) && !anonymous {
+                    debug!("moved {old_hero:?} -> {hero:?}");
+                    self.channel.send(Event::HeroMoved(old_hero, hero)).await?;
+                } else if hero.rank != old_hero.rank && !anonymous {
+                    moved_heros.push((old_hero, hero));
                 }
             }
 
+            if self.track_rank_changes {
+                let rank_changes = rank_changes(moved_heros, &previous_hof, &hall_of_fame);
+                for (old_hero, hero, overtaken_by) in rank_changes {
+                    debug!("rank changed {old_hero:?} -> {hero:?}");
+                    self.channel
+                        .send(Event::RankChanged(old_hero, hero, overtaken_by))
+                        .await?;
+                }
+            }
+
+            for (old_hero, hero) in detect_renames(&mut saved_hof, &mut added_heros) {
+                debug!("renamed {old_hero:?} -> {hero:?}");
+                self.channel
+                    .send(Event::HeroRenamed(old_hero, hero))
+                    .await?;
+            }
+
+            for added_hero in added_heros {
+                let explanation = if added_hero.points_hidden {
+                    Explanation::default()
+                } else {
+                    // Points of a new hero are their whole history, too many
+                    // submissions to weigh every breakdown of
+                    let points = added_hero.points as i32;
+                    self.explain(&added_hero, points, Strategy::Greedy, since, &mut recent)
+                        .await
+                };
+                self.channel
+                    .send(Event::HeroAdded(added_hero, explanation))
+                    .await?;
+            }
+
             for removed_hero in saved_hof {
                 debug!("removed {removed_hero:?}");
                 self.channel.send(Event::HeroRemoved(removed_hero)).await?;
             }
 
             self.store.save_heros(hall_of_fame).await?;
+            self.revision = Some(revision);
+            self.last_poll = Some(now);
             Ok(())
         }
+
+        /// Recent disclosures are fetched on first use and reused for the rest
+        /// of the poll
+        async fn explain(
+            &self,
+            hero: &Hero,
+            points: i32,
+            strategy: Strategy,
+            since: DateTime<Utc>,
+            recent: &mut Option<Vec<DisclosedReport>>,
+        ) -> Explanation {
+            if recent.is_none() {
+                *recent = Some(self.recent_disclosures().await);
+            }
+
+            let recent = recent.as_deref().unwrap_or_default();
+            let weights = PriorityWeights::from_priorities(recent.iter().map(|r| r.priority));
+            let reports = if points > 0 {
+                correlated_reports(hero, recent, since)
+            } else {
+                vec![]
+            };
+
+            let mut breakdowns = match strategy {
+                Strategy::Enumerate => breakdown::breakdowns(points, &self.points_table, &weights),
+                Strategy::Greedy => breakdown::greedy(points, &self.points_table)
+                    .into_iter()
+                    .collect(),
+            };
+            let informational = reports
+                .iter()
+                .filter(|r| r.priority == Priority::P5)
+                .count();
+            breakdown::with_informational(&mut breakdowns, informational as i32);
+
+            Explanation {
+                breakdowns,
+                reports,
+            }
+        }
+
+        async fn recent_disclosures(&self) -> Vec<DisclosedReport> {
+            let recent = self
+                .bugcrowd
+                .disclosed_reports_stream(&self.program_handle, 1)
+                .take(RECENT_DISCLOSURES)
+                .try_collect::<Vec<_>>()
+                .await;
+
+            recent.unwrap_or_else(|error| {
+                warn!("failed to fetch recent disclosures: {error:#}");
+                vec![]
+            })
+        }
+    }
+
+    /// Reports of `hero` disclosed `since`
+    fn correlated_reports(
+        hero: &Hero,
+        recent: &[DisclosedReport],
+        since: DateTime<Utc>,
+    ) -> Vec<DisclosedReport> {
+        recent
+            .iter()
+            .filter(|r| r.researcher_username.as_deref() == Some(hero.username.as_str()))
+            .filter(|r| r.disclosed_at >= since)
+            .cloned()
+            .collect()
+    }
+
+    fn time_delta(duration: Duration) -> TimeDelta {
+        TimeDelta::from_std(duration).unwrap_or(TimeDelta::MAX)
+    }
+
+    /// Pairs up removed and added heros that are most likely the same researcher
+    /// after a username change. Points must be identical and either the profile
+    /// path or the avatar must match; ambiguous candidates are left untouched.
+    /// Matched heros are taken out of both lists.
+    fn detect_renames(removed: &mut Vec<Hero>, added: &mut Vec<Hero>) -> Vec<(Hero, Hero)> {
+        let is_match = |old: &Hero, new: &Hero| {
+            if old.username == ANONYMOUS_HERO_NAME || new.username == ANONYMOUS_HERO_NAME {
+                return false;
+            }
+
+            let same_profile = match (&old.profile_url, &new.profile_url) {
+                (Some(old), Some(new)) => profile_path(old) == profile_path(new),
+                _ => false,
+            };
+            let same_avatar = !old.avatar.is_empty() && old.avatar == new.avatar;
+            old.points == new.points && (same_profile || same_avatar)
+        };
+
+        let mut pairs = vec![];
+        for new_index in 0..added.len() {
+            let new = &added[new_index];
+            let candidates: Vec<usize> = (0..removed.len())
+                .filter(|&old_index| is_match(&removed[old_index], new))
+                .collect();
+            if candidates.len() != 1 {
+                continue;
+            }
+
+            let old_index = candidates[0];
+            let old = &removed[old_index];
+            if added.iter().filter(|new| is_match(old, new)).count() == 1 {
+                pairs.push((old_index, new_index));
+            }
+        }
+
+        let renames = pairs
+            .iter()
+            .map(|&(old_index, new_index)| (removed[old_index].clone(), added[new_index].clone()))
+            .collect();
+
+        let mut index = 0;
+        removed.retain(|_| {
+            index += 1;
+            !pairs.iter().any(|&(old_index, _)| old_index == index - 1)
+        });
+        let mut index = 0;
+        added.retain(|_| {
+            index += 1;
+            !pairs.iter().any(|&(_, new_index)| new_index == index - 1)
+        });
+
+        renames
+    }
+
+    /// Attaches to every moved hero the heros that passed them (drops) or that
+    /// they passed (climbs), and keeps the [`MAX_RANK_CHANGES_PER_POLL`] highest
+    /// ranked ones.
+    fn rank_changes(
+        mut moved: Vec<(Hero, Hero)>,
+        saved_hof: &[Hero],
+        hall_of_fame: &[Hero],
+    ) -> Vec<(Hero, Hero, Vec<Hero>)> {
+        let old_rank = |username: &str| {
+            saved_hof
+                .iter()
+                .find(|h| h.username == username)
+                .map(|h| h.rank)
+        };
+
+        moved.sort_by_key(|(_, hero)| hero.rank);
+        if moved.len() > MAX_RANK_CHANGES_PER_POLL {
+            debug!(
+                "skipping {} rank changes",
+                moved.len() - MAX_RANK_CHANGES_PER_POLL
+            );
+            moved.truncate(MAX_RANK_CHANGES_PER_POLL);
+        }
+
+        moved
+            .into_iter()
+            .map(|(old_hero, hero)| {
+                let dropped = hero.rank > old_hero.rank;
+                let others = hall_of_fame
+                    .iter()
+                    .filter(|other| other.username != hero.username)
+                    .filter(|other| {
+                        let other_old_rank = old_rank(&other.username);
+                        if dropped {
+                            // Was below (or absent), is now above
+                            other.rank < hero.rank
+                                && other_old_rank.is_none_or(|rank| rank > old_hero.rank)
+                        } else {
+                            // Was above, is now below
+                            other.rank > hero.rank
+                                && other_old_rank.is_some_and(|rank| rank < old_hero.rank)
+                        }
+                    })
+                    .cloned()
+                    .collect();
+
+                (old_hero, hero, others)
+            })
+            .collect()
+    }
+
+    /// Usernames that moved ahead of someone on a hidden-points leaderboard.
+    /// Only the relative order of heros present in both polls is compared, so
+    /// entrants and removals don't count as movement.
+    fn climbers(saved_hof: &[Hero], hall_of_fame: &[Hero]) -> Vec<String> {
+        let mut saved_hof: Vec<&Hero> = saved_hof.iter().collect();
+        saved_hof.sort_by_key(|h| h.rank);
+
+        let is_common = |hero: &&Hero, others: &[&Hero]| {
+            hero.username != ANONYMOUS_HERO_NAME
+                && others.iter().any(|h| h.username == hero.username)
+        };
+        let current: Vec<&Hero> = hall_of_fame.iter().collect();
+        let old_order: Vec<&Hero> = saved_hof
+            .iter()
+            .filter(|h| is_common(h, &current))
+            .copied()
+            .collect();
+        let new_order: Vec<&Hero> = current
+            .iter()
+            .filter(|h| is_common(h, &saved_hof))
+            .copied()
+            .collect();
+
+        new_order
+            .iter()
+            .enumerate()
+            .filter(|(new_position, hero)| {
+                old_order
+                    .iter()
+                    .position(|h| h.username == hero.username)
+                    .is_some_and(|old_position| *new_position < old_position)
+            })
+            .map(|(_, hero)| hero.username.clone())
+            .collect()
+    }
+
+    fn profile_path(url: &str) -> &str {
+        let path = url
+            .split_once("://")
+            .map(|(_, rest)| rest.find('/').map_or("", |start| &rest[start..]))
+            .unwrap_or(url);
+        let path = path.split(['?', '#']).next().unwrap_or(path);
+        path.trim_end_matches('/')
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn hero(username: &str, avatar: &str, profile_url: Option<&str>, points: u64) -> Hero {
+            Hero {
+                avatar: avatar.to_string(),
+                profile_url: profile_url.map(str::to_string),
+                username: username.to_string(),
+                points,
+                ..Default::default()
+            }
+        }
+
+        #[test]
+        fn correlates_recent_disclosures() {
+            let now = Utc::now();
+            let report = |id: &str, username: &str, minutes_ago: i64| DisclosedReport {
+                id: id.to_string(),
+                researcher_username: Some(username.to_string()),
+                disclosed_at: now - TimeDelta::minutes(minutes_ago),
+                ..Default::default()
+            };
+            let recent = [
+                report("a", "alice", 2),
+                report("b", "bob", 2),
+                report("c", "alice", 60 * 24),
+            ];
+
+            let alice = hero("alice", "", None, 40);
+            let since = now - TimeDelta::minutes(10);
+            let ids: Vec<String> = correlated_reports(&alice, &recent, since)
+                .into_iter()
+                .map(|r| r.id)
+                .collect();
+            assert_eq!(ids, ["a"]);
+        }
+
+        #[test]
+        fn rename_by_avatar() {
+            let mut removed = vec![hero("alice", "https://cdn/a.png", None, 40)];
+            let mut added = vec![
+                hero("bob", "https://cdn/b.png", None, 40),
+                hero("alice2", "https://cdn/a.png", None, 40),
+            ];
+
+            let renames = detect_renames(&mut removed, &mut added);
+            assert_eq!(renames.len(), 1);
+            assert_eq!(renames[0].0.username, "alice");
+            assert_eq!(renames[0].1.username, "alice2");
+            assert!(removed.is_empty());
+            assert_eq!(added.len(), 1);
+            assert_eq!(added[0].username, "bob");
+        }
+
+        #[test]
+        fn rename_by_profile_path() {
+            let mut removed = vec![hero("alice", "", Some("https://bugcrowd.com/h/1234"), 10)];
+            let mut added = vec![hero("alice2", "", Some("/h/1234/"), 10)];
+
+            let renames = detect_renames(&mut removed, &mut added);
+            assert_eq!(renames.len(), 1);
+        }
+
+        #[test]
+        fn overtaken() {
+            let ranked = |username: &str, points: u64, rank: usize| Hero {
+                rank,
+                ..hero(username, "", None, points)
+            };
+            let saved = vec![ranked("a", 30, 1), ranked("b", 20, 2), ranked("c", 10, 3)];
+            let current = vec![ranked("c", 50, 1), ranked("a", 30, 2), ranked("b", 20, 3)];
+            let moved = vec![
+                (saved[0].clone(), current[1].clone()),
+                (saved[1].clone(), current[2].clone()),
+            ];
+
+            let changes = rank_changes(moved, &saved, &current);
+            assert_eq!(changes.len(), 2);
+            assert_eq!(changes[0].1.username, "a");
+            assert_eq!(changes[0].2.len(), 1);
+            assert_eq!(changes[0].2[0].username, "c");
+        }
+
+        #[test]
+        fn hidden_points_climber() {
+            let ranked = |username: &str, rank: usize| Hero {
+                rank,
+                ..hero(username, "", None, 0)
+            };
+            let saved = vec![
+                ranked("a", 1),
+                ranked("b", 2),
+                ranked("c", 3),
+                ranked("d", 4),
+            ];
+            let current = vec![
+                ranked("new", 1),
+                ranked("a", 2),
+                ranked("d", 3),
+                ranked("b", 4),
+            ];
+
+            assert_eq!(climbers(&saved, &current), ["d"]);
+        }
+
+        #[test]
+        fn ambiguous_is_ignored() {
+            let mut removed = vec![
+                hero("alice", "https://cdn/default.png", None, 5),
+                hero("bob", "https://cdn/default.png", None, 5),
+            ];
+            let mut added = vec![hero("carol", "https://cdn/default.png", None, 5)];
+
+            assert!(detect_renames(&mut removed, &mut added).is_empty());
+        }
     }
 }
 
 pub mod disclosed_reports {
-    use bugcrowd_api::{client::BugcrowdApi, models::DisclosedReport};
-    use log::debug;
+    use bugcrowd_api::{
+        client::BugcrowdApi,
+        models::{DisclosedReport, DisclosureCursor},
+    };
+    use chrono::Utc;
+    use log::{debug, warn};
     use tokio::sync::mpsc::Sender;
 
-    use crate::store::DisclosedReportsStore;
+    use crate::store::{Confirmation, DisclosedReportsStore};
+
+    /// Pages fetched per poll while looking for the cursor
+    const MAX_PAGES: u64 = 5;
 
     pub struct Poller {
         pub bugcrowd: BugcrowdApi,
         pub store: DisclosedReportsStore,
         pub program_handle: String,
         pub channel: Sender<Event>,
+        /// More new reports than this at once are withheld until confirmed
+        pub max_new_reports: usize,
     }
 
     #[derive(Debug)]
     pub enum Event {
-        ReportDisclosed(DisclosedReport),
+        ReportDisclosed(Box<DisclosedReport>),
+        ReportsWithheld { count: usize, confirm_key: String },
     }
 
     impl Poller {
         pub async fn poll(&mut self) -> Result<(), anyhow::Error> {
             debug!("polling disclosed reports");
 
-            let last_disclosed = self.store.last_disclosed_report().await?;
-            debug!("last_disclosed: {last_disclosed:?}");
+            let cursor = self.store.cursor().await?;
+            debug!("cursor: {cursor:?}");
 
-            if last_disclosed.is_none() {
+            if cursor.is_none() {
                 let report = self
                     .bugcrowd
                     .last_disclosed_report(&self.program_handle)
                     .await?;
-                let last_disclosed_report = report.unwrap_or(DisclosedReport {
-                    id: String::from("0"),
-                    title: String::from("stub"),
-                    ..Default::default()
-                });
-                debug!("saving last disclosed report: {last_disclosed_report:?}");
-                self.store
-                    .set_last_disclosed_report(&last_disclosed_report)
-                    .await?;
+                let mut cursor = DisclosureCursor {
+                    disclosed_at: Utc::now(),
+                    seen_ids: vec![],
+                };
+                if let Some(report) = report {
+                    cursor.disclosed_at = report.disclosed_at;
+                    cursor.seen_ids.push(report.id);
+                }
+
+                debug!("saving disclosed reports cursor: {cursor:?}");
+                self.store.set_cursor(&cursor).await?;
                 return Ok(());
             }
 
-            let last_disclosed = last_disclosed.unwrap();
+            let mut cursor = cursor.unwrap();
             let new_disclosed = self
                 .bugcrowd
-                .disclosed_reports_after(&self.program_handle, &last_disclosed.id)
+                .disclosed_reports_after(&self.program_handle, &cursor, MAX_PAGES)
                 .await?;
-            if !new_disclosed.is_empty() {
-                let last_disclosed = new_disclosed.first().cloned().unwrap();
-                for disclosed in new_disclosed {
-                    debug!("new disclosed report: {disclosed:?}");
-                    self.channel.send(Event::ReportDisclosed(disclosed)).await?;
+            if new_disclosed.is_empty() {
+                return Ok(());
+            }
+
+            if new_disclosed.len() > self.max_new_reports {
+                match self.store.take_confirmation().await? {
+                    Some(Confirmation::Send) => {}
+                    Some(Confirmation::Skip) => {
+                        warn!("skipping {} disclosed reports", new_disclosed.len());
+                        cursor.advance(&new_disclosed);
+                        self.store.set_cursor(&cursor).await?;
+                        self.store.set_withheld(0).await?;
+                        return Ok(());
+                    }
+                    None => {
+                        let count = new_disclosed.len();
+                        warn!(
+                            "withholding {count} disclosed reports (limit {}), waiting for confirmation",
+                            self.max_new_reports
+                        );
+                        if self.store.withheld().await? != count {
+                            let confirm_key = self.store.confirm_key();
+                            self.channel
+                                .send(Event::ReportsWithheld { count, confirm_key })
+                                .await?;
+                            self.store.set_withheld(count).await?;
+                        }
+                        return Ok(());
+                    }
+                }
+            }
+
+            cursor.advance(&new_disclosed);
+            for disclosed in new_disclosed {
+                debug!("new disclosed report: {disclosed:?}");
+                self.channel
+                    .send(Event::ReportDisclosed(Box::new(disclosed)))
+                    .await?;
+            }
+
+            self.store.set_cursor(&cursor).await?;
+            self.store.set_withheld(0).await?;
+            Ok(())
+        }
+    }
+}
+
+pub mod crowdstream {
+    use bugcrowd_api::{
+        client::BugcrowdApi,
+        models::{ActivityKind, CrowdStreamActivity},
+    };
+    use log::debug;
+    use tokio::sync::mpsc::Sender;
+
+    use crate::store::CrowdStreamStore;
+
+    /// Pages fetched per poll while looking for already seen activities
+    const MAX_PAGES: u64 = 5;
+
+    pub struct Poller {
+        pub bugcrowd: BugcrowdApi,
+        pub store: CrowdStreamStore,
+        pub program_handle: String,
+        pub channel: Sender<Event>,
+        /// Activity kinds sent to the channel, disclosures have their own poller
+        pub activity: Vec<ActivityKind>,
+    }
+
+    #[derive(Debug)]
+    pub enum Event {
+        SubmissionAccepted(CrowdStreamActivity),
+        SubmissionRewarded(CrowdStreamActivity),
+    }
+
+    impl Poller {
+        pub async fn poll(&mut self) -> Result<(), anyhow::Error> {
+            debug!("polling crowdstream activity");
+
+            let seen = self.store.seen().await?;
+            // Stores from before the initialized key only have seen entries
+            let first_poll = !self.store.initialized().await? && seen.is_empty();
+            let mut new_activities = vec![];
+
+            for page in 1..=MAX_PAGES {
+                let activities = self
+                    .bugcrowd
+                    .crowdstream_activities(&self.program_handle, page)
+                    .await?;
+                let results_len = activities.results.len();
+
+                let mut found_seen = false;
+                for activity in activities.results {
+                    if CrowdStreamStore::is_seen(&seen, &activity) {
+                        found_seen = true;
+                    } else {
+                        new_activities.push(activity);
+                    }
+                }
+
+                if first_poll || found_seen || results_len == 0 {
+                    break;
+                }
+            }
+
+            if !first_poll {
+                for activity in new_activities.iter().rev() {
+                    if !self.activity.contains(&activity.kind()) {
+                        continue;
+                    }
+                    let event = match activity.kind() {
+                        ActivityKind::Accepted => Event::SubmissionAccepted(activity.clone()),
+                        ActivityKind::Rewarded => Event::SubmissionRewarded(activity.clone()),
+                        ActivityKind::Disclosed => continue,
+                    };
+
+                    debug!("new crowdstream activity: {activity:?}");
+                    self.channel.send(event).await?;
+                }
+            }
+
+            self.store.add_seen(&new_activities).await?;
+            if first_poll {
+                self.store.set_initialized().await?;
+            }
+            Ok(())
+        }
+    }
+}
+
+pub mod announcements {
+    use bugcrowd_api::{client::BugcrowdApi, models::Announcement};
+    use log::debug;
+    use tokio::sync::mpsc::Sender;
+
+    use crate::store::AnnouncementsStore;
+
+    pub struct Poller {
+        pub bugcrowd: BugcrowdApi,
+        pub store: AnnouncementsStore,
+        pub program_handle: String,
+        pub channel: Sender<Event>,
+    }
+
+    #[derive(Debug)]
+    pub enum Event {
+        Announced(Announcement),
+    }
+
+    impl Poller {
+        pub async fn poll(&mut self) -> Result<(), anyhow::Error> {
+            debug!("polling announcements");
+
+            let announcements = self.bugcrowd.announcements(&self.program_handle).await?;
+            let seen = self.store.seen().await?;
+            // Stores from before the initialized key only have seen ids
+            let first_poll = !self.store.initialized().await? && seen.is_empty();
+
+            let listed_ids: Vec<String> = announcements.iter().map(|a| a.id.clone()).collect();
+            let new_announcements: Vec<Announcement> = announcements
+                .into_iter()
+                .filter(|a| !seen.contains(&a.id))
+                .collect();
+
+            if !first_poll {
+                for announcement in new_announcements.into_iter().rev() {
+                    debug!("new announcement: {announcement:?}");
+                    self.channel.send(Event::Announced(announcement)).await?;
+                }
+            }
+
+            self.store.save_seen(&listed_ids).await?;
+            Ok(())
+        }
+    }
+}
+
+pub mod brief {
+    use bugcrowd_api::{
+        client::BugcrowdApi,
+        models::{EngagementBrief, Priority, RewardRange, Target},
+    };
+    use log::debug;
+    use tokio::sync::mpsc::Sender;
+
+    use crate::store::BriefStore;
+
+    pub struct Poller {
+        pub bugcrowd: BugcrowdApi,
+        pub store: BriefStore,
+        pub program_handle: String,
+        pub channel: Sender<Event>,
+    }
+
+    #[derive(Debug, PartialEq)]
+    pub enum Event {
+        TargetAdded(Target),
+        TargetRemoved(Target),
+        /// Target moved in or out of scope (old, new)
+        TargetScopeChanged(Target, Target),
+        /// Reward range of a priority changed, `None` when the priority isn't listed
+        RewardChanged(Option<RewardRange>, Option<RewardRange>),
+        StatusChanged(String, String),
+    }
+
+    impl Poller {
+        pub async fn poll(&mut self) -> Result<(), anyhow::Error> {
+            debug!("polling engagement brief");
+
+            let brief = self.bugcrowd.engagement_brief(&self.program_handle).await?;
+            let saved_brief = self.store.brief().await?;
+
+            if let Some(saved_brief) = saved_brief {
+                if saved_brief == brief {
+                    return Ok(());
+                }
+
+                for event in diff(&saved_brief, &brief) {
+                    debug!("brief changed: {event:?}");
+                    self.channel.send(event).await?;
+                }
+            }
+
+            self.store.set_brief(&brief).await?;
+            Ok(())
+        }
+    }
+
+    fn diff(old: &EngagementBrief, new: &EngagementBrief) -> Vec<Event> {
+        let mut events = vec![];
+
+        if old.status != new.status {
+            events.push(Event::StatusChanged(old.status.clone(), new.status.clone()));
+        }
+
+        for target in &new.targets {
+            match old.targets.iter().find(|t| t.name == target.name) {
+                None => events.push(Event::TargetAdded(target.clone())),
+                Some(old_target) if old_target.in_scope != target.in_scope => events.push(
+                    Event::TargetScopeChanged(old_target.clone(), target.clone()),
+                ),
+                Some(_) => {}
+            }
+        }
+        for target in &old.targets {
+            if !new.targets.iter().any(|t| t.name == target.name) {
+                events.push(Event::TargetRemoved(target.clone()));
+            }
+        }
+
+        let mut priorities: Vec<Priority> = old
+            .rewards
+            .iter()
+            .chain(&new.rewards)
+            .map(|r| r.priority)
+            .collect();
+        priorities.sort();
+        priorities.dedup();
+        for priority in priorities {
+            let old_reward = old.rewards.iter().find(|r| r.priority == priority);
+            let new_reward = new.rewards.iter().find(|r| r.priority == priority);
+            if old_reward != new_reward {
+                events.push(Event::RewardChanged(
+                    old_reward.cloned(),
+                    new_reward.cloned(),
+                ));
+            }
+        }
+
+        events
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn target(name: &str, in_scope: bool) -> Target {
+            Target {
+                name: name.to_string(),
+                in_scope,
+                category: None,
+            }
+        }
+
+        #[test]
+        fn scope_and_rewards() {
+            let old = EngagementBrief {
+                status: "live".to_string(),
+                targets: vec![
+                    target("www.example.com", true),
+                    target("old.example.com", true),
+                ],
+                rewards: vec![RewardRange {
+                    priority: Priority::P1,
+                    min: Some(2500),
+                    max: Some(5000),
+                }],
+            };
+            let new = EngagementBrief {
+                status: "live".to_string(),
+                targets: vec![
+                    target("www.example.com", false),
+                    target("api.example.com", true),
+                ],
+                rewards: vec![RewardRange {
+                    priority: Priority::P1,
+                    min: Some(2500),
+                    max: Some(10000),
+                }],
+            };
+
+            let events = diff(&old, &new);
+            assert_eq!(
+                events,
+                [
+                    Event::TargetScopeChanged(
+                        target("www.example.com", true),
+                        target("www.example.com", false)
+                    ),
+                    Event::TargetAdded(target("api.example.com", true)),
+                    Event::TargetRemoved(target("old.example.com", true)),
+                    Event::RewardChanged(
+                        Some(old.rewards[0].clone()),
+                        Some(new.rewards[0].clone())
+                    ),
+                ]
+            );
+        }
+    }
+}
+
+pub mod engagements {
+    use bugcrowd_api::{
+        client::BugcrowdApi,
+        models::{Engagement, EngagementKind},
+    };
+    use log::debug;
+    use tokio::sync::mpsc::Sender;
+
+    use crate::store::EngagementsStore;
+
+    pub struct Poller {
+        pub bugcrowd: BugcrowdApi,
+        pub store: EngagementsStore,
+        pub channel: Sender<Event>,
+        /// Only track these engagement types, all when empty
+        pub kinds: Vec<EngagementKind>,
+        /// Only track engagements whose handle or name match, `*` is a wildcard
+        pub pattern: Option<String>,
+    }
+
+    #[derive(Debug)]
+    pub enum Event {
+        /// A private engagement became visible to the session
+        Invited(Engagement),
+        /// An engagement is no longer visible to the session
+        Disappeared(String),
+    }
+
+    impl Poller {
+        /// Returns the engagements that should be tracked
+        pub async fn poll(&mut self) -> Result<Vec<Engagement>, anyhow::Error> {
+            debug!("polling engagements");
+
+            let engagements = self.bugcrowd.engagements().await?;
+            let known = self.store.handles().await?;
+
+            if !known.is_empty() {
+                for engagement in &engagements {
+                    if engagement.is_private && !known.iter().any(|h| h == engagement.handle()) {
+                        debug!("invited to {engagement:?}");
+                        self.channel
+                            .send(Event::Invited(engagement.clone()))
+                            .await?;
+                    }
+                }
+
+                for handle in known {
+                    if !engagements.iter().any(|e| e.handle() == handle) {
+                        debug!("{handle} disappeared");
+                        self.channel.send(Event::Disappeared(handle)).await?;
+                    }
+                }
+            }
+
+            let handles: Vec<String> = engagements.iter().map(|e| e.handle().to_string()).collect();
+            self.store.save_handles(&handles).await?;
+
+            let engagements = engagements
+                .into_iter()
+                .filter(|e| self.is_tracked(e))
+                .collect();
+            Ok(engagements)
+        }
+
+        fn is_tracked(&self, engagement: &Engagement) -> bool {
+            let kind_matches = self.kinds.is_empty()
+                || engagement
+                    .kind()
+                    .is_some_and(|kind| self.kinds.contains(&kind));
+            let pattern_matches = self.pattern.as_deref().is_none_or(|pattern| {
+                wildcard_match(pattern, engagement.handle())
+                    || wildcard_match(pattern, &engagement.name)
+            });
+
+            kind_matches && pattern_matches
+        }
+    }
+
+    /// Case-insensitive match where `*` matches any run of characters
+    fn wildcard_match(pattern: &str, text: &str) -> bool {
+        let pattern = pattern.to_lowercase();
+        let text = text.to_lowercase();
+        let mut parts = pattern.split('*');
+
+        let first = parts.next().unwrap_or_default();
+        let Some(mut rest) = text.strip_prefix(first) else {
+            return false;
+        };
+
+        let parts: Vec<&str> = parts.collect();
+        for (index, part) in parts.iter().enumerate() {
+            if index == parts.len() - 1 {
+                return rest.ends_with(part);
+            }
+
+            match rest.find(part) {
+                Some(position) => rest = &rest[position + part.len()..],
+                None => return false,
+            }
+        }
+
+        rest.is_empty()
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn wildcard() {
+            assert!(wildcard_match("tesla", "Tesla"));
+            assert!(wildcard_match("*-vdp", "acme-vdp"));
+            assert!(wildcard_match("a*m*e", "acme"));
+            assert!(!wildcard_match("a*", "bca"));
+            assert!(!wildcard_match("tesla", "tesla-mvp"));
+        }
+    }
+}
+
+pub mod researchers {
+    use bugcrowd_api::{
+        client::BugcrowdApi,
+        models::{DisclosedReport, Hero, ResearcherProfile},
+    };
+    use log::{debug, warn};
+    use tokio::sync::{mpsc::Sender, watch};
+
+    use crate::store::{HallOfFameStore, RedisConnection, ResearcherStore};
+
+    pub struct Poller {
+        pub bugcrowd: BugcrowdApi,
+        pub redis_connection: RedisConnection,
+        pub channel: Sender<Event>,
+        /// Usernames of the followed researchers
+        pub usernames: Vec<String>,
+        /// Handles of the programs currently tracked
+        pub programs: watch::Receiver<Vec<String>>,
+    }
+
+    #[derive(Debug)]
+    pub enum Event {
+        /// Followed researcher appeared in the hall of fame of a tracked program
+        AppearedInHallOfFame(String, Hero),
+        PointsGained(ResearcherProfile, ResearcherProfile),
+        ReportDisclosed(String, DisclosedReport),
+    }
+
+    impl Poller {
+        pub async fn poll(&mut self) -> Result<(), anyhow::Error> {
+            if self.usernames.is_empty() {
+                return Ok(());
+            }
+            debug!("polling researchers");
+
+            let programs = self.programs.borrow().clone();
+            let mut hall_of_fames = vec![];
+            for program_handle in programs {
+                let mut store = HallOfFameStore {
+                    program_handle: program_handle.clone(),
+                    redis_connection: self.redis_connection.clone(),
+                };
+                hall_of_fames.push((program_handle, store.heros().await?));
+            }
+
+            for username in self.usernames.clone() {
+                // A renamed or private profile shouldn't hold back the others
+                if let Err(error) = self.poll_researcher(&username, &hall_of_fames).await {
+                    warn!("failed to poll researcher {username}: {error:#}");
+                }
+            }
+
+            Ok(())
+        }
+
+        async fn poll_researcher(
+            &mut self,
+            username: &str,
+            hall_of_fames: &[(String, Vec<Hero>)],
+        ) -> Result<(), anyhow::Error> {
+            let mut store = ResearcherStore {
+                username: username.to_string(),
+                redis_connection: self.redis_connection.clone(),
+            };
+            let saved_profile = store.profile().await?;
+            let first_poll = saved_profile.is_none();
+
+            let seen_programs = store.programs().await?;
+            for (program_handle, heros) in hall_of_fames {
+                if seen_programs.contains(program_handle) {
+                    continue;
+                }
+
+                let hero = heros
+                    .iter()
+                    .find(|h| h.username.eq_ignore_ascii_case(username));
+                if let Some(hero) = hero {
+                    if !first_poll {
+                        debug!("{username} appeared in {program_handle}");
+                        self.channel
+                            .send(Event::AppearedInHallOfFame(
+                                program_handle.clone(),
+                                hero.clone(),
+                            ))
+                            .await?;
+                    }
+                    store.add_program(program_handle).await?;
+                }
+            }
+
+            let profile = self.bugcrowd.researcher(username).await?;
+            if let Some(saved_profile) = saved_profile {
+                for report in &profile.disclosures {
+                    if !saved_profile.disclosures.iter().any(|r| r.id == report.id) {
+                        debug!("{username} disclosed {report:?}");
+                        self.channel
+                            .send(Event::ReportDisclosed(username.to_string(), report.clone()))
+                            .await?;
+                    }
+                }
+
+                if profile.stats.points > saved_profile.stats.points {
+                    debug!("{username} gained points");
+                    self.channel
+                        .send(Event::PointsGained(saved_profile, profile.clone()))
+                        .await?;
+                }
+            }
+
+            store.set_profile(&profile).await?;
+
+            Ok(())
+        }
+    }
+}
+
+pub mod session {
+    use std::{path::PathBuf, time::Duration, time::SystemTime};
+
+    use bugcrowd_api::{client::BugcrowdApi, session::SessionState};
+    use log::{debug, info, warn};
+    use tokio::{
+        signal::unix::{SignalKind, signal},
+        sync::{mpsc::Sender, watch},
+    };
+
+    use crate::store::SessionStore;
+
+    pub struct Watcher {
+        pub state: watch::Receiver<SessionState>,
+        pub channel: Sender<Event>,
+    }
+
+    #[derive(Debug)]
+    pub enum Event {
+        /// The configured session is no longer authenticated
+        Expired,
+        Restored(SessionState),
+    }
+
+    impl Watcher {
+        /// Runs until the client is dropped
+        pub async fn watch(&mut self) -> Result<(), anyhow::Error> {
+            let mut expired = false;
+
+            while self.state.changed().await.is_ok() {
+                let state = self.state.borrow_and_update().clone();
+                debug!("session state: {state:?}");
+
+                match state {
+                    SessionState::Unauthenticated if !expired => {
+                        expired = true;
+                        self.channel.send(Event::Expired).await?;
+                    }
+                    SessionState::Authenticated(_) if expired => {
+                        expired = false;
+                        self.channel.send(Event::Restored(state)).await?;
+                    }
+                    _ => {}
                 }
+            }
 
+            Ok(())
+        }
+    }
+
+    /// Swaps the session token at runtime from `file` (on SIGHUP or when the
+    /// file changes) and persists refreshes sent back by Bugcrowd, to `file`
+    /// when set, otherwise to Redis
+    pub struct TokenSync {
+        pub bugcrowd: BugcrowdApi,
+        pub store: SessionStore,
+        pub file: Option<PathBuf>,
+        /// Token passed on the command line, refreshes are saved against it
+        pub configured_token: Option<String>,
+    }
+
+    impl TokenSync {
+        const CHECK_FILE_INTERVAL: Duration = Duration::from_secs(30);
+
+        pub async fn run(&mut self) -> Result<(), anyhow::Error> {
+            let mut token = self.bugcrowd.session_token();
+            // SIGHUP also reloads the config, only listen when there's a file
+            let mut hangup = match self.file {
+                Some(_) => Some(signal(SignalKind::hangup())?),
+                None => None,
+            };
+            let mut check_file = tokio::time::interval(Self::CHECK_FILE_INTERVAL);
+            let mut modified = self.file_modified().await;
+
+            loop {
+                tokio::select! {
+                    _ = async { hangup.as_mut().unwrap().recv().await }, if hangup.is_some() => {
+                        info!("received SIGHUP, reloading session token");
+                        self.reload().await;
+                    }
+
+                    _ = check_file.tick(), if self.file.is_some() => {
+                        let file_modified = self.file_modified().await;
+                        if file_modified != modified {
+                            info!("session token file changed, reloading");
+                            modified = file_modified;
+                            self.reload().await;
+                        }
+                    }
+
+                    changed = token.changed() => {
+                        if changed.is_err() {
+                            return Ok(());
+                        }
+
+                        let token = token.borrow_and_update().clone();
+                        if let Some(token) = token {
+                            self.persist(&token).await?;
+                            modified = self.file_modified().await;
+                        }
+                    }
+                }
+            }
+        }
+
+        async fn reload(&mut self) {
+            let Some(file) = &self.file else {
+                return;
+            };
+
+            match tokio::fs::read_to_string(file).await {
+                Ok(token) if !token.trim().is_empty() => {
+                    self.bugcrowd.set_session_token(token.trim().to_string());
+                }
+                Ok(_) => warn!("session token file {} is empty", file.display()),
+                Err(error) => warn!("failed to read {}: {error:#?}", file.display()),
+            }
+        }
+
+        async fn persist(&mut self, token: &str) -> Result<(), anyhow::Error> {
+            if let Some(file) = &self.file {
+                let saved = tokio::fs::read_to_string(file).await.unwrap_or_default();
+                if saved.trim() != token {
+                    debug!("saving session token to {}", file.display());
+                    tokio::fs::write(file, format!("{token}\n")).await?;
+                }
+            } else if let Some(configured_token) = &self.configured_token {
+                debug!("saving refreshed session token");
                 self.store
-                    .set_last_disclosed_report(&last_disclosed)
+                    .save_refreshed_token(configured_token, token)
                     .await?;
             }
 
             Ok(())
         }
+
+        async fn file_modified(&self) -> Option<SystemTime> {
+            let file = self.file.as_ref()?;
+            let metadata = tokio::fs::metadata(file).await.ok()?;
+            metadata.modified().ok()
+        }
     }
 }
@@ -0,0 +1,353 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+    time::Duration,
+};
+
+use bugcrowd_api::{client::BugcrowdApi, models::ActivityKind};
+use log::{error, info};
+use tokio::{sync::watch, task::JoinHandle};
+
+use crate::{
+    breakdown::PointsTable,
+    config::{Arguments, Source},
+    poll,
+    store::{
+        AnnouncementsStore, BriefStore, CrowdStreamStore, DisclosedReportsStore, HallOfFameStore,
+        RedisConnection,
+    },
+    webhook::{self, Sink},
+};
+
+/// Dependencies shared by every tracked program
+#[derive(Clone)]
+pub struct Context {
+    pub bugcrowd: BugcrowdApi,
+    pub redis: RedisConnection,
+    /// Replaced as a whole when the config file is reloaded
+    pub config: watch::Receiver<Arc<Arguments>>,
+}
+
+impl Context {
+    pub fn sink(&self, source: Source, program: Option<&str>) -> Sink {
+        Sink {
+            config: self.config.clone(),
+            source,
+            program: program.map(str::to_string),
+        }
+    }
+}
+
+/// A running poller, it stops between polls once dropped so a poll is never
+/// cut short
+struct Poll {
+    _stop: watch::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+/// Pollers running for a single program
+pub struct Program {
+    pub program_handle: String,
+    polls: BTreeMap<Source, Poll>,
+}
+
+impl Program {
+    fn new(program_handle: &str) -> Self {
+        Self {
+            program_handle: program_handle.to_string(),
+            polls: BTreeMap::new(),
+        }
+    }
+
+    /// Starts the pollers `config` enables for the program and stops the
+    /// others. Intervals and the other settings are read before every poll,
+    /// running pollers are left alone
+    pub fn sync(&mut self, context: &Context, config: &Arguments) {
+        let sources = Self::sources(config, &self.program_handle);
+        self.polls.retain(|source, _| {
+            let enabled = sources.contains(source);
+            if !enabled {
+                info!("stopping {source:?} poller of {}", self.program_handle);
+            }
+            enabled
+        });
+
+        for source in sources {
+            if !self.polls.contains_key(&source) {
+                let poll = Self::spawn(context, &self.program_handle, source);
+                self.polls.insert(source, poll);
+            }
+        }
+    }
+
+    /// Sources polled for `program_handle`
+    fn sources(config: &Arguments, program_handle: &str) -> Vec<Source> {
+        let activity = &config.crowdstream_activity;
+        let mut sources = vec![Source::HallOfFame, Source::Announcements];
+        if activity.contains(&ActivityKind::Disclosed) {
+            sources.push(Source::DisclosedReports);
+        }
+        if activity.iter().any(|kind| *kind != ActivityKind::Disclosed) {
+            sources.push(Source::Crowdstream);
+        }
+        if config.track_brief || config.program(program_handle).track_brief {
+            sources.push(Source::Brief);
+        }
+        sources
+    }
+
+    fn spawn(context: &Context, program_handle: &str, source: Source) -> Poll {
+        let (stop, mut stopped) = watch::channel(());
+        let config = context.config.clone();
+        let program_handle = program_handle.to_string();
+        let sink = context.sink(source, Some(&program_handle));
+
+        let task = match source {
+            // ==== Hall Of Fame ====
+            Source::HallOfFame => {
+                let store = HallOfFameStore {
+                    program_handle: program_handle.clone(),
+                    redis_connection: context.redis.clone(),
+                };
+                let channel = webhook::hall_of_fame::background_channel(sink);
+                let mut poller = poll::hall_of_fame::Poller {
+                    bugcrowd: context.bugcrowd.clone(),
+                    store,
+                    program_handle: program_handle.clone(),
+                    channel,
+                    track_rank_changes: false,
+                    revision: None,
+                    points_table: PointsTable::default(),
+                    last_poll: None,
+                    interval: Duration::ZERO,
+                    disclosures_interval: Duration::ZERO,
+                };
+
+                tokio::task::spawn(async move {
+                    info!("started polling hall of fame");
+
+                    loop {
+                        {
+                            let config = config.borrow();
+                            let intervals = config.program(&program_handle).intervals;
+                            poller.track_rank_changes = config.track_rank_changes;
+                            poller.points_table = config.points_table(&program_handle);
+                            poller.interval = intervals.hall_of_fame;
+                            poller.disclosures_interval = intervals.disclosed_reports;
+                        }
+                        poller.poll().await.expect("hall of fame poll failed");
+
+                        tokio::select! {
+                            _ = tokio::time::sleep(poller.interval) => {}
+                            _ = stopped.changed() => break,
+                        }
+                    }
+
+                    info!("stopped polling hall of fame");
+                })
+            }
+
+            // ==== Crowdstream Disclosed Reports ====
+            Source::DisclosedReports => {
+                let store = DisclosedReportsStore {
+                    program_handle: program_handle.clone(),
+                    redis_connection: context.redis.clone(),
+                };
+                let channel = webhook::disclosed_reports::background_channel(sink);
+                let mut poller = poll::disclosed_reports::Poller {
+                    bugcrowd: context.bugcrowd.clone(),
+                    store,
+                    program_handle: program_handle.clone(),
+                    channel,
+                    max_new_reports: 0,
+                };
+
+                tokio::task::spawn(async move {
+                    info!("started polling Crowdstream disclosed reports");
+
+                    loop {
+                        let interval = {
+                            let config = config.borrow();
+                            poller.max_new_reports = config.max_new_disclosures;
+                            config.program(&program_handle).intervals.disclosed_reports
+                        };
+                        poller
+                            .poll()
+                            .await
+                            .expect("Crowdstream disclosed reports poll failed");
+
+                        tokio::select! {
+                            _ = tokio::time::sleep(interval) => {}
+                            _ = stopped.changed() => break,
+                        }
+                    }
+
+                    info!("stopped polling Crowdstream disclosed reports");
+                })
+            }
+
+            // ==== Crowdstream Activity ====
+            Source::Crowdstream => {
+                let store = CrowdStreamStore {
+                    program_handle: program_handle.clone(),
+                    redis_connection: context.redis.clone(),
+                };
+                let channel = webhook::crowdstream::background_channel(sink);
+                let mut poller = poll::crowdstream::Poller {
+                    bugcrowd: context.bugcrowd.clone(),
+                    store,
+                    program_handle: program_handle.clone(),
+                    channel,
+                    activity: vec![],
+                };
+
+                tokio::task::spawn(async move {
+                    info!("started polling Crowdstream activity");
+
+                    loop {
+                        let interval = {
+                            let config = config.borrow();
+                            poller.activity = config.crowdstream_activity.clone();
+                            config.program(&program_handle).intervals.crowdstream
+                        };
+                        poller
+                            .poll()
+                            .await
+                            .expect("Crowdstream activity poll failed");
+
+                        tokio::select! {
+                            _ = tokio::time::sleep(interval) => {}
+                            _ = stopped.changed() => break,
+                        }
+                    }
+
+                    info!("stopped polling Crowdstream activity");
+                })
+            }
+
+            // ==== Announcements ====
+            Source::Announcements => {
+                let store = AnnouncementsStore {
+                    program_handle: program_handle.clone(),
+                    redis_connection: context.redis.clone(),
+                };
+                let channel =
+                    webhook::announcements::background_channel(sink, program_handle.clone());
+                let mut poller = poll::announcements::Poller {
+                    bugcrowd: context.bugcrowd.clone(),
+                    store,
+                    program_handle: program_handle.clone(),
+                    channel,
+                };
+
+                tokio::task::spawn(async move {
+                    info!("started polling announcements");
+
+                    loop {
+                        let interval = config
+                            .borrow()
+                            .program(&program_handle)
+                            .intervals
+                            .announcements;
+                        poller.poll().await.expect("announcements poll failed");
+
+                        tokio::select! {
+                            _ = tokio::time::sleep(interval) => {}
+                            _ = stopped.changed() => break,
+                        }
+                    }
+
+                    info!("stopped polling announcements");
+                })
+            }
+
+            // ==== Engagement Brief ====
+            Source::Brief => {
+                let store = BriefStore {
+                    program_handle: program_handle.clone(),
+                    redis_connection: context.redis.clone(),
+                };
+                let channel = webhook::brief::background_channel(sink, program_handle.clone());
+                let mut poller = poll::brief::Poller {
+                    bugcrowd: context.bugcrowd.clone(),
+                    store,
+                    program_handle: program_handle.clone(),
+                    channel,
+                };
+
+                tokio::task::spawn(async move {
+                    info!("started polling engagement brief");
+
+                    loop {
+                        let interval = config.borrow().program(&program_handle).intervals.brief;
+                        if let Err(error) = poller.poll().await {
+                            error!("engagement brief poll failed: {error:#}");
+                        }
+
+                        tokio::select! {
+                            _ = tokio::time::sleep(interval) => {}
+                            _ = stopped.changed() => break,
+                        }
+                    }
+
+                    info!("stopped polling engagement brief");
+                })
+            }
+
+            Source::Engagements | Source::Researchers | Source::Session => {
+                unreachable!("{source:?} isn't polled per program")
+            }
+        };
+
+        Poll { _stop: stop, task }
+    }
+
+    /// Pollers only stop by panicking, stopped ones are no longer listed
+    pub fn is_finished(&self) -> bool {
+        self.polls.values().any(|poll| poll.task.is_finished())
+    }
+
+    /// Pollers finish the poll they're in
+    pub fn stop(self) {
+        info!("stopped tracking {}", self.program_handle);
+    }
+}
+
+/// Tracked programs by handle
+#[derive(Default)]
+pub struct Programs {
+    running: HashMap<String, Program>,
+}
+
+impl Programs {
+    pub fn contains(&self, program_handle: &str) -> bool {
+        self.running.contains_key(program_handle)
+    }
+
+    /// Starts and stops programs so exactly `handles` are tracked, and their
+    /// pollers so they match `config`
+    pub fn sync(&mut self, context: &Context, config: &Arguments, handles: &[String]) {
+        let stopped: Vec<String> = self
+            .running
+            .keys()
+            .filter(|handle| !handles.contains(handle))
+            .cloned()
+            .collect();
+        for handle in stopped {
+            self.running.remove(&handle).unwrap().stop();
+        }
+
+        for handle in handles {
+            let program = self.running.entry(handle.clone()).or_insert_with(|| {
+                info!("tracking {handle}");
+                Program::new(handle)
+            });
+            program.sync(context, config);
+        }
+    }
+
+    /// Program whose pollers stopped, they only stop by panicking
+    pub fn finished(&self) -> Option<&Program> {
+        self.running.values().find(|program| program.is_finished())
+    }
+}
@@ -1,7 +1,10 @@
-use bugcrowd_api::models::{DisclosedReport, Hero};
+use bugcrowd_api::models::{
+    CrowdStreamActivity, DisclosedReport, DisclosureCursor, EngagementBrief, Hero,
+    ResearcherProfile,
+};
 use redis::AsyncCommands;
 
-type RedisConnection = redis::aio::ConnectionManager;
+pub type RedisConnection = redis::aio::ConnectionManager;
 
 // ==== Hall Of Fame ====
 pub struct HallOfFameStore {
@@ -45,27 +48,332 @@ pub struct DisclosedReportsStore {
     pub redis_connection: RedisConnection,
 }
 
+/// Operator decision for a withheld batch of disclosed reports
+#[derive(Debug, PartialEq, Eq)]
+pub enum Confirmation {
+    Send,
+    Skip,
+}
+
 impl DisclosedReportsStore {
     fn key(&self) -> String {
         format!("bugcrowd_tracker:/disclosed/{}", self.program_handle)
     }
 
-    pub async fn last_disclosed_report(
+    fn cursor_key(&self) -> String {
+        format!("{}/cursor", self.key())
+    }
+
+    pub fn confirm_key(&self) -> String {
+        format!("{}/confirm", self.key())
+    }
+
+    fn withheld_key(&self) -> String {
+        format!("{}/withheld", self.key())
+    }
+
+    pub async fn cursor(&mut self) -> Result<Option<DisclosureCursor>, anyhow::Error> {
+        let cursor: Option<String> = self.redis_connection.get(self.cursor_key()).await?;
+        if let Some(cursor) = cursor {
+            return Ok(Some(serde_json::from_str(&cursor)?));
+        }
+
+        // Older versions only saved the last disclosed report
+        let report: Option<String> = self.redis_connection.get(self.key()).await?;
+        let cursor = report
+            .map(|report| serde_json::from_str::<DisclosedReport>(&report))
+            .transpose()?
+            .map(|report| DisclosureCursor {
+                disclosed_at: report.disclosed_at,
+                seen_ids: vec![report.id],
+            });
+        Ok(cursor)
+    }
+
+    pub async fn set_cursor(&mut self, cursor: &DisclosureCursor) -> Result<(), anyhow::Error> {
+        let key = self.cursor_key();
+        let cursor = serde_json::to_string(cursor)?;
+        self.redis_connection.set::<_, _, ()>(key, cursor).await?;
+        Ok(())
+    }
+
+    /// Takes the pending confirmation, if the operator set one
+    pub async fn take_confirmation(&mut self) -> Result<Option<Confirmation>, anyhow::Error> {
+        let confirmation: Option<String> =
+            self.redis_connection.get_del(self.confirm_key()).await?;
+        Ok(match confirmation.as_deref().map(str::trim) {
+            Some("send") => Some(Confirmation::Send),
+            Some("skip") => Some(Confirmation::Skip),
+            _ => None,
+        })
+    }
+
+    /// Number of reports that were last withheld, used to notify only once per batch
+    pub async fn withheld(&mut self) -> Result<usize, anyhow::Error> {
+        let withheld: Option<usize> = self.redis_connection.get(self.withheld_key()).await?;
+        Ok(withheld.unwrap_or(0))
+    }
+
+    pub async fn set_withheld(&mut self, withheld: usize) -> Result<(), anyhow::Error> {
+        let key = self.withheld_key();
+        self.redis_connection.set::<_, _, ()>(key, withheld).await?;
+        Ok(())
+    }
+}
+
+// ==== Crowdstream Activity ====
+pub struct CrowdStreamStore {
+    pub program_handle: String,
+    pub redis_connection: RedisConnection,
+}
+
+impl CrowdStreamStore {
+    /// Number of seen activities remembered, older entries are trimmed
+    const MAX_SEEN: isize = 500;
+
+    fn key(&self) -> String {
+        format!("bugcrowd_tracker:/crowdstream/{}", self.program_handle)
+    }
+
+    fn initialized_key(&self) -> String {
+        format!("{}/initialized", self.key())
+    }
+
+    /// Whether the first poll recorded the activity already on the Crowdstream,
+    /// seen entries alone can't tell for programs without activity yet
+    pub async fn initialized(&mut self) -> Result<bool, anyhow::Error> {
+        let initialized: bool = self.redis_connection.exists(self.initialized_key()).await?;
+        Ok(initialized)
+    }
+
+    pub async fn set_initialized(&mut self) -> Result<(), anyhow::Error> {
+        let key = self.initialized_key();
+        self.redis_connection.set::<_, _, ()>(key, 1).await?;
+        Ok(())
+    }
+
+    /// Activities change kind (accepted -> rewarded -> disclosed) without
+    /// changing id, so both are part of the seen entry
+    fn seen_entry(activity: &CrowdStreamActivity) -> String {
+        format!("{}:{}", activity.kind(), activity.id)
+    }
+
+    pub async fn seen(&mut self) -> Result<Vec<String>, anyhow::Error> {
+        let key = self.key();
+        let seen: Vec<String> = self.redis_connection.lrange(key, 0, -1).await?;
+        Ok(seen)
+    }
+
+    pub fn is_seen(seen: &[String], activity: &CrowdStreamActivity) -> bool {
+        seen.contains(&Self::seen_entry(activity))
+    }
+
+    pub async fn add_seen(
+        &mut self,
+        activities: &[CrowdStreamActivity],
+    ) -> Result<(), anyhow::Error> {
+        if activities.is_empty() {
+            return Ok(());
+        }
+
+        let mut pipeline = redis::pipe();
+        let key = self.key();
+
+        for activity in activities.iter().rev() {
+            pipeline.lpush(&key, Self::seen_entry(activity)).ignore();
+        }
+        pipeline.ltrim(&key, 0, Self::MAX_SEEN - 1).ignore();
+
+        pipeline.exec_async(&mut self.redis_connection).await?;
+        Ok(())
+    }
+}
+
+// ==== Engagement Brief ====
+pub struct BriefStore {
+    pub program_handle: String,
+    pub redis_connection: RedisConnection,
+}
+
+impl BriefStore {
+    fn key(&self) -> String {
+        format!("bugcrowd_tracker:/brief/{}", self.program_handle)
+    }
+
+    pub async fn brief(&mut self) -> Result<Option<EngagementBrief>, anyhow::Error> {
+        let key = self.key();
+        let brief: Option<String> = self.redis_connection.get(key).await?;
+        let brief = brief
+            .map(|brief| serde_json::from_str(&brief))
+            .transpose()?;
+        Ok(brief)
+    }
+
+    pub async fn set_brief(&mut self, brief: &EngagementBrief) -> Result<(), anyhow::Error> {
+        let key = self.key();
+        let brief = serde_json::to_string(brief)?;
+        self.redis_connection.set::<_, _, ()>(key, brief).await?;
+        Ok(())
+    }
+}
+
+// ==== Announcements ====
+pub struct AnnouncementsStore {
+    pub program_handle: String,
+    pub redis_connection: RedisConnection,
+}
+
+impl AnnouncementsStore {
+    fn key(&self) -> String {
+        format!("bugcrowd_tracker:/announcements/{}", self.program_handle)
+    }
+
+    fn initialized_key(&self) -> String {
+        format!("{}/initialized", self.key())
+    }
+
+    pub async fn seen(&mut self) -> Result<Vec<String>, anyhow::Error> {
+        let key = self.key();
+        let seen: Vec<String> = self.redis_connection.smembers(key).await?;
+        Ok(seen)
+    }
+
+    /// Whether the first poll recorded the announcements already posted,
+    /// seen ids alone can't tell for programs without announcements yet
+    pub async fn initialized(&mut self) -> Result<bool, anyhow::Error> {
+        let initialized: bool = self.redis_connection.exists(self.initialized_key()).await?;
+        Ok(initialized)
+    }
+
+    /// Replaces the seen ids with the ids currently listed, so removed
+    /// announcements don't accumulate
+    pub async fn save_seen(&mut self, ids: &[String]) -> Result<(), anyhow::Error> {
+        let mut pipeline = redis::pipe();
+        let key = self.key();
+
+        pipeline.atomic().del(&key).ignore();
+        if !ids.is_empty() {
+            pipeline.sadd(&key, ids).ignore();
+        }
+        pipeline.set(self.initialized_key(), 1).ignore();
+
+        pipeline.exec_async(&mut self.redis_connection).await?;
+        Ok(())
+    }
+}
+
+// ==== Engagements ====
+pub struct EngagementsStore {
+    pub redis_connection: RedisConnection,
+}
+
+impl EngagementsStore {
+    fn key(&self) -> String {
+        "bugcrowd_tracker:/engagements".to_string()
+    }
+
+    pub async fn handles(&mut self) -> Result<Vec<String>, anyhow::Error> {
+        let key = self.key();
+        let handles: Vec<String> = self.redis_connection.smembers(key).await?;
+        Ok(handles)
+    }
+
+    pub async fn save_handles(&mut self, handles: &[String]) -> Result<(), anyhow::Error> {
+        let mut pipeline = redis::pipe();
+        let key = self.key();
+
+        pipeline.del(&key).ignore();
+        if !handles.is_empty() {
+            pipeline.sadd(&key, handles).ignore();
+        }
+
+        pipeline.exec_async(&mut self.redis_connection).await?;
+        Ok(())
+    }
+}
+
+// ==== Researchers ====
+pub struct ResearcherStore {
+    pub username: String,
+    pub redis_connection: RedisConnection,
+}
+
+impl ResearcherStore {
+    fn key(&self) -> String {
+        format!("bugcrowd_tracker:/researcher/{}", self.username)
+    }
+
+    fn programs_key(&self) -> String {
+        format!("{}/programs", self.key())
+    }
+
+    pub async fn profile(&mut self) -> Result<Option<ResearcherProfile>, anyhow::Error> {
+        let key = self.key();
+        let profile: Option<String> = self.redis_connection.get(key).await?;
+        let profile = profile.map(|p| serde_json::from_str(&p)).transpose()?;
+        Ok(profile)
+    }
+
+    pub async fn set_profile(&mut self, profile: &ResearcherProfile) -> Result<(), anyhow::Error> {
+        let key = self.key();
+        let profile = serde_json::to_string(profile)?;
+        self.redis_connection.set::<_, _, ()>(key, profile).await?;
+        Ok(())
+    }
+
+    /// Programs whose hall of fame the researcher was already seen in
+    pub async fn programs(&mut self) -> Result<Vec<String>, anyhow::Error> {
+        let key = self.programs_key();
+        let programs: Vec<String> = self.redis_connection.smembers(key).await?;
+        Ok(programs)
+    }
+
+    pub async fn add_program(&mut self, program_handle: &str) -> Result<(), anyhow::Error> {
+        let key = self.programs_key();
+        self.redis_connection
+            .sadd::<_, _, ()>(key, program_handle)
+            .await?;
+        Ok(())
+    }
+}
+
+// ==== Session ====
+pub struct SessionStore {
+    pub redis_connection: RedisConnection,
+}
+
+impl SessionStore {
+    fn key(&self) -> String {
+        "bugcrowd_tracker:/session".to_string()
+    }
+
+    /// Latest refresh of `configured_token`, if Bugcrowd sent one
+    pub async fn refreshed_token(
         &mut self,
-    ) -> Result<Option<DisclosedReport>, anyhow::Error> {
+        configured_token: &str,
+    ) -> Result<Option<String>, anyhow::Error> {
         let key = self.key();
-        let report: Option<String> = self.redis_connection.get(key).await?;
-        let report = report.map(|report| serde_json::from_str(&report).unwrap());
-        Ok(report)
+        let (configured, token): (Option<String>, Option<String>) = redis::pipe()
+            .hget(&key, "configured")
+            .hget(&key, "token")
+            .query_async(&mut self.redis_connection)
+            .await?;
+
+        Ok(token.filter(|_| configured.as_deref() == Some(configured_token)))
     }
 
-    pub async fn set_last_disclosed_report(
+    pub async fn save_refreshed_token(
         &mut self,
-        report: &DisclosedReport,
+        configured_token: &str,
+        token: &str,
     ) -> Result<(), anyhow::Error> {
         let key = self.key();
-        let report = serde_json::to_string(report)?;
-        self.redis_connection.set::<_, _, ()>(key, report).await?;
+        self.redis_connection
+            .hset_multiple::<_, _, _, ()>(
+                key,
+                &[("configured", configured_token), ("token", token)],
+            )
+            .await?;
         Ok(())
     }
 }
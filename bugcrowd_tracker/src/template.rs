@@ -0,0 +1,211 @@
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+use twilight_model::channel::message::Embed;
+
+const CONTENT_LIMIT: usize = 2000;
+const TITLE_LIMIT: usize = 256;
+const DESCRIPTION_LIMIT: usize = 4096;
+
+/// Wording of the messages of a source. `{title}`, `{description}`, `{url}`,
+/// `{fields.<name>}`, `{source}`, `{program}` and the models of the event like
+/// `{report.substate}` are replaced by the values of each event, `{{` and `}}`
+/// are literal braces
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Template {
+    /// Message text above the embed, e.g. to mention a role
+    pub content: Option<Text>,
+    /// Replaces the title of the embed
+    pub title: Option<Text>,
+    /// Replaces the description of the embed
+    pub description: Option<Text>,
+}
+
+impl Template {
+    /// Message content and embed of an event, `variables` are added to the
+    /// ones taken from the embed
+    pub fn apply(
+        &self,
+        mut embed: Embed,
+        variables: Map<String, Value>,
+    ) -> (Option<String>, Embed) {
+        let fields: Map<String, Value> = embed
+            .fields
+            .iter()
+            .map(|field| (field.name.clone(), Value::String(field.value.clone())))
+            .collect();
+        let mut all = json!({
+            "title": embed.title,
+            "description": embed.description,
+            "url": embed.url,
+            "fields": fields,
+        });
+        all.as_object_mut().unwrap().extend(variables);
+
+        let render = |text: &Text, limit| {
+            let rendered = text.render(&all);
+            let rendered: String = rendered.chars().take(limit).collect();
+            (!rendered.trim().is_empty()).then_some(rendered)
+        };
+        let content = self
+            .content
+            .as_ref()
+            .and_then(|text| render(text, CONTENT_LIMIT));
+        if let Some(title) = &self.title {
+            embed.title = render(title, TITLE_LIMIT);
+        }
+        if let Some(description) = &self.description {
+            embed.description = render(description, DESCRIPTION_LIMIT);
+        }
+        (content, embed)
+    }
+}
+
+/// Parsed template string
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Text {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    /// Dotted path into the variables
+    Variable(Vec<String>),
+}
+
+impl TryFrom<String> for Text {
+    type Error = String;
+
+    fn try_from(template: String) -> Result<Self, Self::Error> {
+        let mut parts = vec![];
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some('{') | None => {
+                                return Err(format!("unclosed placeholder {{{name}"));
+                            }
+                            Some(c) => name.push(c),
+                        }
+                    }
+
+                    let path: Vec<String> = name.trim().split('.').map(str::to_string).collect();
+                    if path.iter().any(|key| key.is_empty()) {
+                        return Err(format!("invalid placeholder {{{name}}}"));
+                    }
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Variable(path));
+                }
+                '}' => return Err("unmatched `}`, use `}}` for a literal brace".to_string()),
+                c => literal.push(c),
+            }
+        }
+
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(Self { parts })
+    }
+}
+
+impl Text {
+    /// Missing variables are left empty
+    fn render(&self, variables: &Value) -> String {
+        let mut rendered = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(literal) => rendered.push_str(literal),
+                Part::Variable(path) => {
+                    let value = path.iter().try_fold(variables, |value, key| match value {
+                        Value::Array(items) => items.get(key.parse::<usize>().ok()?),
+                        value => value.get(key),
+                    });
+                    match value {
+                        Some(Value::String(text)) => rendered.push_str(text),
+                        Some(Value::Null) | None => {}
+                        Some(value) => rendered.push_str(&value.to_string()),
+                    }
+                }
+            }
+        }
+        rendered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bugcrowd_api::models::DisclosedReport;
+    use twilight_util::builder::embed::{EmbedBuilder, EmbedFieldBuilder};
+
+    use super::*;
+
+    fn text(template: &str) -> Text {
+        Text::try_from(template.to_string()).unwrap()
+    }
+
+    #[test]
+    fn apply() {
+        let template = Template {
+            content: Some(text("<@&42> {fields.Severity} on {program}")),
+            title: Some(text("{{{title}}}")),
+            description: Some(text("{missing}")),
+        };
+        let embed = EmbedBuilder::new()
+            .title("Stored XSS")
+            .description("details")
+            .field(EmbedFieldBuilder::new("Severity", "High (P2)"))
+            .build();
+        let variables = Map::from_iter([("program".to_string(), json!("acme"))]);
+
+        let (content, embed) = template.apply(embed, variables);
+        assert_eq!(content.as_deref(), Some("<@&42> High (P2) on acme"));
+        assert_eq!(embed.title.as_deref(), Some("{Stored XSS}"));
+        assert_eq!(embed.description, None);
+    }
+
+    #[test]
+    fn unmodeled_fields() {
+        let report: DisclosedReport = serde_json::from_value(json!({
+            "id": "1",
+            "title": "IDOR",
+            "disclosed_at": "2025-01-02T03:04:05Z",
+            "disclosure_report_url": "/disclosures/1",
+            "substate": "resolved",
+        }))
+        .unwrap();
+        let template = Template {
+            content: Some(text("{report.title} ({report.substate})")),
+            ..Template::default()
+        };
+
+        let variables = Map::from_iter([("report".to_string(), json!(report))]);
+        let (content, _) = template.apply(EmbedBuilder::new().build(), variables);
+        assert_eq!(content.as_deref(), Some("IDOR (resolved)"));
+    }
+
+    #[test]
+    fn invalid_templates() {
+        assert!(Text::try_from("{title".to_string()).is_err());
+        assert!(Text::try_from("title}".to_string()).is_err());
+        assert!(Text::try_from("{fields.}".to_string()).is_err());
+        assert_eq!(text("{{}}").render(&json!({})), "{}");
+    }
+}
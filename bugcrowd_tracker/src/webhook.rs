@@ -1,20 +1,101 @@
-use std::{sync::LazyLock, time::Duration};
+use std::{
+    sync::{Arc, LazyLock},
+    time::Duration,
+};
 
 use log::info;
 use reqwest::{Client, ClientBuilder};
-use serde_json::json;
+use serde_json::{Map, Value, json};
+use tokio::sync::watch;
 use twilight_model::{
     channel::message::Embed,
     id::{Id, marker::WebhookMarker},
 };
 
-#[derive(Debug, Clone)]
+use crate::{
+    config::{Arguments, Source},
+    template::Template,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Webhook {
     pub id: Id<WebhookMarker>,
     pub token: String,
 }
 
-pub async fn send_webhook(webhook: &Webhook, embed: Embed) -> Result<(), anyhow::Error> {
+impl Webhook {
+    /// https://discord.com/api/webhooks/<id>/<token>
+    pub fn from_url(url: &str) -> Option<Self> {
+        let path_parts: Vec<&str> = url.trim_start_matches("https://").split('/').collect();
+        if path_parts.len() >= 5 && path_parts[1] == "api" && path_parts[2] == "webhooks" {
+            let webhook_id = path_parts[3].parse::<u64>().ok()?;
+            let token = path_parts[4];
+
+            Some(Webhook {
+                id: Id::new_checked(webhook_id)?,
+                token: token.to_string(),
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// Where the events of a source go, looked up for every event so reloaded
+/// sinks, routes and templates apply right away
+#[derive(Debug, Clone)]
+pub struct Sink {
+    pub config: watch::Receiver<Arc<Arguments>>,
+    pub source: Source,
+    pub program: Option<String>,
+}
+
+impl Sink {
+    pub fn destination(&self) -> Destination {
+        let config = self.config.borrow();
+        Destination {
+            webhook: config.webhook(self.source, self.program.as_deref()),
+            template: config.templates.get(&self.source).cloned(),
+            source: self.source,
+            program: self.program.clone(),
+        }
+    }
+}
+
+/// Webhook and template the message of an event is sent with
+#[derive(Debug, Clone)]
+pub struct Destination {
+    pub webhook: Webhook,
+    template: Option<Template>,
+    source: Source,
+    program: Option<String>,
+}
+
+impl Destination {
+    /// `event` holds the models of the event for the template, e.g.
+    /// `{"report": ...}`, unmodeled Bugcrowd fields included
+    pub async fn send(&self, embed: Embed, event: Value) -> Result<(), anyhow::Error> {
+        let Some(template) = &self.template else {
+            return send_webhook(&self.webhook, None, embed).await;
+        };
+
+        let mut variables = Map::from_iter([
+            ("source".to_string(), json!(self.source)),
+            ("program".to_string(), json!(self.program)),
+        ]);
+        if let Value::Object(event) = event {
+            variables.extend(event);
+        }
+        let (content, embed) = template.apply(embed, variables);
+        send_webhook(&self.webhook, content.as_deref(), embed).await
+    }
+}
+
+pub async fn send_webhook(
+    webhook: &Webhook,
+    content: Option<&str>,
+    embed: Embed,
+) -> Result<(), anyhow::Error> {
     static REQUEST_CLIENT: LazyLock<Client> = LazyLock::new(|| {
         ClientBuilder::new()
             .timeout(Duration::from_secs(5))
@@ -28,7 +109,10 @@ pub async fn send_webhook(webhook: &Webhook, embed: Embed) -> Result<(), anyhow:
             "https://discord.com/api/v9/webhooks/{}/{}?wait=true",
             webhook.id, webhook.token
         ))
-        .json(&json!({ "embeds": [embed] }))
+        .json(&match content {
+            Some(content) => json!({ "content": content, "embeds": [embed] }),
+            None => json!({ "embeds": [embed] }),
+        })
         .send()
         .await?;
     if response.status() == 429 {
@@ -47,7 +131,7 @@ pub async fn send_webhook(webhook: &Webhook, embed: Embed) -> Result<(), anyhow:
 
         let retry_after = Duration::from_secs(retry_after);
         tokio::time::sleep(retry_after).await;
-        return Box::pin(send_webhook(webhook, embed)).await;
+        return Box::pin(send_webhook(webhook, content, embed)).await;
     }
 
     response.error_for_status()?;
@@ -55,23 +139,24 @@ pub async fn send_webhook(webhook: &Webhook, embed: Embed) -> Result<(), anyhow:
 }
 
 pub mod hall_of_fame {
-    use bugcrowd_api::models::{ANONYMOUS_HERO_NAME, Hero};
+    use bugcrowd_api::models::{ANONYMOUS_HERO_NAME, DisclosedReport, Hero};
     use log::{debug, error, info};
+    use serde_json::json;
     use tokio::sync::mpsc::{Sender, channel};
     use twilight_util::builder::embed::{EmbedBuilder, EmbedFooterBuilder};
 
     use crate::{
         breakdown,
         poll::hall_of_fame::Event,
-        webhook::{Webhook, send_webhook},
+        webhook::{Destination, Sink},
     };
 
-    pub fn background_channel(webhook: Webhook) -> Sender<Event> {
+    pub fn background_channel(sink: Sink) -> Sender<Event> {
         let (sender, mut receiver) = channel(1);
         tokio::task::spawn(async move {
             while let Some(event) = receiver.recv().await {
                 info!("recieved event {event:?}");
-                if let Err(error) = handle_event(&webhook, event).await {
+                if let Err(error) = handle_event(&sink.destination(), event).await {
                     error!("failed to handle event {error:#?}");
                     break;
                 }
@@ -85,14 +170,25 @@ pub mod hall_of_fame {
     const SUCCESS_COLOR: u32 = 5294200;
     const FAILURE_COLOR: u32 = 16711680;
 
-    async fn handle_event(webhook: &Webhook, event: Event) -> Result<(), anyhow::Error> {
+    async fn handle_event(destination: &Destination, event: Event) -> Result<(), anyhow::Error> {
+        let variables = match &event {
+            Event::HeroAdded(hero, _) | Event::HeroRemoved(hero) => json!({ "hero": hero }),
+            Event::HeroUpdated(old, new, _)
+            | Event::HeroRenamed(old, new)
+            | Event::HeroMoved(old, new)
+            | Event::RankChanged(old, new, _) => json!({ "hero": new, "previous": old }),
+        };
         let embed = match event {
-            Event::HeroAdded(hero) => {
+            Event::HeroAdded(hero, explanation) => {
                 let _hero = display(&hero);
-                let breakdown = breakdown::calculate_points_breakdown(hero.points as i32);
+                let breakdown = breakdown::describe(&explanation.breakdowns);
 
-                let content = if let Some(breakdown) = breakdown {
-                    let breakdown = breakdown.to_string();
+                let mut content = if hero.points_hidden {
+                    format!(
+                        "{_hero} was added to the leaderboard\n-# (rank: #{})",
+                        hero.rank
+                    )
+                } else if let Some(breakdown) = breakdown {
                     format!(
                         "{_hero} was added to the leaderboard with **{} points ({breakdown})**\n-# (rank: #{})",
                         hero.points, hero.rank
@@ -103,6 +199,7 @@ pub mod hall_of_fame {
                         hero.points, hero.rank
                     )
                 };
+                content.push_str(&disclosures(&explanation.reports));
 
                 EmbedBuilder::new()
                     .color(BUGCROWD_THEME_COLOR)
@@ -123,22 +220,80 @@ pub mod hall_of_fame {
                     .build()
             }
 
-            Event::HeroUpdated(old, new) => {
+            Event::HeroRenamed(old, new) => {
+                let old_hero = display(&old);
+                let new_hero = display(&new);
+                let content = format!("{old_hero} is now known as {new_hero}");
+                EmbedBuilder::new()
+                    .color(BUGCROWD_THEME_COLOR)
+                    .description(content)
+                    .footer(EmbedFooterBuilder::new(format!("rank: {}", new.rank)))
+                    .validate()?
+                    .build()
+            }
+
+            Event::HeroMoved(old, new) => {
+                let _hero = display(&new);
+                let content = format!(
+                    "{_hero} moved up the leaderboard from #{} to #{}",
+                    old.rank, new.rank
+                );
+                EmbedBuilder::new()
+                    .color(SUCCESS_COLOR)
+                    .description(content)
+                    .validate()?
+                    .build()
+            }
+
+            Event::RankChanged(old, new, others) => {
+                let _hero = display(&new);
+                let others = others.iter().map(display).collect::<Vec<_>>().join(", ");
+                let content = if new.rank > old.rank {
+                    if others.is_empty() {
+                        format!("{_hero} dropped from #{} to #{}", old.rank, new.rank)
+                    } else {
+                        format!(
+                            "{_hero} dropped from #{} to #{} after {others} overtook them",
+                            old.rank, new.rank
+                        )
+                    }
+                } else if others.is_empty() {
+                    format!("{_hero} moved up from #{} to #{}", old.rank, new.rank)
+                } else {
+                    format!(
+                        "{_hero} moved up from #{} to #{} past {others}",
+                        old.rank, new.rank
+                    )
+                };
+
+                EmbedBuilder::new()
+                    .color(BUGCROWD_THEME_COLOR)
+                    .description(content)
+                    .validate()?
+                    .build()
+            }
+
+            Event::HeroUpdated(old, new, explanation) => {
                 if old.points > new.points {
                     // Lost points
                     let lost = old.points - new.points;
                     let _hero = display(&new);
-                    let content = format!(
+                    let mut content = format!(
                         "{_hero} lost **-{lost} points** and now has **{} points**",
                         new.points
                     );
+                    if let Some(explanation) =
+                        breakdown::describe_loss(lost, &explanation.breakdowns)
+                    {
+                        content.push_str(&format!("\n-# {explanation}"));
+                    }
                     let mut embed = EmbedBuilder::new()
                         .color(FAILURE_COLOR)
                         .description(content);
 
                     if old.rank != new.rank {
                         let rank_change = (old.rank as isize) - (new.rank as isize);
-                        let footer = format!("#{} -> #{} (-{}))", old.rank, new.rank, rank_change);
+                        let footer = format!("#{} -> #{} ({rank_change:+})", old.rank, new.rank);
                         embed = embed.footer(EmbedFooterBuilder::new(footer));
                     }
 
@@ -147,10 +302,9 @@ pub mod hall_of_fame {
                     // Gained points
                     let gained = new.points - old.points;
                     let _hero = display(&new);
-                    let breakdown = breakdown::calculate_points_breakdown(gained as i32);
+                    let breakdown = breakdown::describe(&explanation.breakdowns);
 
-                    let content = if let Some(breakdown) = breakdown {
-                        let breakdown = breakdown.to_string();
+                    let mut content = if let Some(breakdown) = breakdown {
                         format!(
                             "{_hero} gained **+{gained} points ({breakdown})** and now has **{} points**",
                             new.points
@@ -161,6 +315,7 @@ pub mod hall_of_fame {
                             new.points
                         )
                     };
+                    content.push_str(&disclosures(&explanation.reports));
 
                     let mut embed = EmbedBuilder::new()
                         .color(SUCCESS_COLOR)
@@ -168,7 +323,7 @@ pub mod hall_of_fame {
 
                     if old.rank != new.rank {
                         let rank_change = (old.rank as isize) - (new.rank as isize);
-                        let footer = format!("#{} -> #{} (+{})", old.rank, new.rank, rank_change);
+                        let footer = format!("#{} -> #{} ({rank_change:+})", old.rank, new.rank);
                         embed = embed.footer(EmbedFooterBuilder::new(footer));
                     }
 
@@ -178,10 +333,40 @@ pub mod hall_of_fame {
         };
 
         debug!("sending embed: {embed:?}");
-        send_webhook(webhook, embed).await?;
+        destination.send(embed, variables).await?;
         Ok(())
     }
 
+    /// Links to the reports a points change was correlated with
+    fn disclosures(reports: &[DisclosedReport]) -> String {
+        reports
+            .iter()
+            .map(|report| {
+                format!(
+                    "\n-# disclosed: [{}](https://bugcrowd.com{}) ({})",
+                    escape_link_text(&report.title),
+                    report
+                        .disclosure_report_url
+                        .replace('(', "%28")
+                        .replace(')', "%29"),
+                    report.priority
+                )
+            })
+            .collect()
+    }
+
+    /// Brackets in a title would end the link text early
+    fn escape_link_text(text: &str) -> String {
+        let mut escaped = String::with_capacity(text.len());
+        for c in text.chars() {
+            if matches!(c, '\\' | '[' | ']') {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+        }
+        escaped
+    }
+
     fn display(hero: &Hero) -> String {
         if let Some(profile_url) = hero.profile_url.as_ref() {
             format!("[**`{}`**]({})", hero.username, profile_url)
@@ -191,25 +376,44 @@ pub mod hall_of_fame {
             format!("**`{}`**", hero.username)
         }
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn disclosure_links() {
+            let report = DisclosedReport {
+                title: "XSS in [search](beta)".to_string(),
+                disclosure_report_url: "/disclosures/(1)".to_string(),
+                ..Default::default()
+            };
+            assert_eq!(
+                disclosures(&[report]),
+                "\n-# disclosed: [XSS in \\[search\\](beta)](https://bugcrowd.com/disclosures/%281%29) (P?)"
+            );
+        }
+    }
 }
 
 pub mod disclosed_reports {
     use chrono_humanize::HumanTime;
     use log::{debug, error, info};
+    use serde_json::json;
     use tokio::sync::mpsc::{Sender, channel};
     use twilight_util::builder::embed::{EmbedBuilder, EmbedFieldBuilder, EmbedFooterBuilder};
 
     use crate::{
         poll::disclosed_reports::Event,
-        webhook::{Webhook, send_webhook},
+        webhook::{Destination, Sink},
     };
 
-    pub fn background_channel(webhook: Webhook) -> Sender<Event> {
+    pub fn background_channel(sink: Sink) -> Sender<Event> {
         let (sender, mut receiver) = channel(1);
         tokio::task::spawn(async move {
             while let Some(event) = receiver.recv().await {
                 info!("recieved event {event:?}");
-                if let Err(error) = handle_event(&webhook, event).await {
+                if let Err(error) = handle_event(&sink.destination(), event).await {
                     error!("failed to handle event {error:#?}");
                     break;
                 }
@@ -218,7 +422,14 @@ pub mod disclosed_reports {
 
         sender
     }
-    async fn handle_event(webhook: &Webhook, event: Event) -> Result<(), anyhow::Error> {
+
+    const BUGCROWD_THEME_COLOR: u32 = 15886370;
+
+    async fn handle_event(destination: &Destination, event: Event) -> Result<(), anyhow::Error> {
+        let variables = match &event {
+            Event::ReportDisclosed(report) => json!({ "report": report }),
+            Event::ReportsWithheld { count, .. } => json!({ "count": count }),
+        };
         let embed = match event {
             Event::ReportDisclosed(report) => {
                 let url = format!("https://bugcrowd.com{}", report.disclosure_report_url);
@@ -227,15 +438,14 @@ pub mod disclosed_reports {
                 } else {
                     "Anonymous".to_string()
                 };
-                let severity = format!(
-                    "{} (P{})",
-                    priority_as_string(report.priority),
-                    report.priority
-                );
-                let bounty = report.amount.unwrap_or(String::from("Unknown"));
+                let severity = report.priority.severity();
+                let bounty = report
+                    .amount
+                    .map(|amount| amount.to_string())
+                    .unwrap_or(String::from("Unknown"));
 
                 let embed = EmbedBuilder::new()
-                    .color(2368553)
+                    .color(report.priority.color())
                     .title(report.title)
                     .url(url)
                     .field(EmbedFieldBuilder::new("Reporter", reporter))
@@ -246,27 +456,621 @@ pub mod disclosed_reports {
                     .field(EmbedFieldBuilder::new("Severity", severity).inline())
                     .field(EmbedFieldBuilder::new("Bounty Award", bounty).inline());
 
-                let reported = HumanTime::from(report.created_at);
                 let disclosed = HumanTime::from(report.disclosed_at);
-                let embed = embed.footer(EmbedFooterBuilder::new(format!(
-                    "Reported {reported}, disclosed {disclosed}"
-                )));
+                let footer = match report.created_at {
+                    Some(created_at) => {
+                        format!(
+                            "Reported {}, disclosed {disclosed}",
+                            HumanTime::from(created_at)
+                        )
+                    }
+                    None => format!("Disclosed {disclosed}"),
+                };
+                let embed = embed.footer(EmbedFooterBuilder::new(footer));
                 embed.build()
             }
+
+            Event::ReportsWithheld { count, confirm_key } => {
+                let content = format!(
+                    "**{count}** reports look newly disclosed at once, so they were withheld.\n\
+                     Set the Redis key `{confirm_key}` to `send` to post them, or `skip` to ignore them."
+                );
+                EmbedBuilder::new()
+                    .color(BUGCROWD_THEME_COLOR)
+                    .description(content)
+                    .validate()?
+                    .build()
+            }
         };
 
         debug!("sending embed: {embed:?}");
-        send_webhook(webhook, embed).await?;
+        destination.send(embed, variables).await?;
         Ok(())
     }
+}
+
+pub mod crowdstream {
+    use log::{debug, error, info};
+    use serde_json::json;
+    use tokio::sync::mpsc::{Sender, channel};
+    use twilight_util::builder::embed::{EmbedBuilder, EmbedFieldBuilder};
+
+    use crate::{
+        poll::crowdstream::Event,
+        webhook::{Destination, Sink},
+    };
 
-    fn priority_as_string(severity: u32) -> &'static str {
-        match severity {
-            1 => "Critical",
-            2 => "High",
-            3 => "Medium",
-            4 => "Low",
-            _ => "Informative",
+    pub fn background_channel(sink: Sink) -> Sender<Event> {
+        let (sender, mut receiver) = channel(1);
+        tokio::task::spawn(async move {
+            while let Some(event) = receiver.recv().await {
+                info!("recieved event {event:?}");
+                if let Err(error) = handle_event(&sink.destination(), event).await {
+                    error!("failed to handle event {error:#?}");
+                    break;
+                }
+            }
+        });
+
+        sender
+    }
+
+    const ACCEPTED_COLOR: u32 = 3447003;
+    const REWARDED_COLOR: u32 = 5294200;
+
+    async fn handle_event(destination: &Destination, event: Event) -> Result<(), anyhow::Error> {
+        let (activity, color) = match &event {
+            Event::SubmissionAccepted(activity) => (activity, ACCEPTED_COLOR),
+            Event::SubmissionRewarded(activity) => (activity, REWARDED_COLOR),
+        };
+
+        let reporter = if let Some(username) = &activity.researcher_username {
+            format!("[**`{}`**](https://bugcrowd.com/h/{})", username, username)
+        } else {
+            "An anonymous researcher".to_string()
+        };
+        let severity = activity.priority.severity();
+        let content = match &event {
+            Event::SubmissionAccepted(_) => {
+                format!("{reporter} had a submission accepted at **{severity}**")
+            }
+            Event::SubmissionRewarded(activity) => format!(
+                "{reporter} was rewarded at **{severity}** for **{}**",
+                activity
+                    .amount
+                    .as_ref()
+                    .map(|amount| amount.to_string())
+                    .unwrap_or(String::from("an unknown amount"))
+            ),
+        };
+
+        let mut embed = EmbedBuilder::new().color(color).description(content);
+        if let Some(title) = &activity.title {
+            embed = embed.title(title);
+        }
+        if let Some(target) = &activity.target {
+            embed = embed.field(EmbedFieldBuilder::new("Target", target));
+        }
+
+        let embed = embed.validate()?.build();
+        debug!("sending embed: {embed:?}");
+        destination
+            .send(embed, json!({ "activity": activity }))
+            .await?;
+        Ok(())
+    }
+}
+
+pub mod announcements {
+    use log::{debug, error, info};
+    use serde_json::json;
+    use tokio::sync::mpsc::{Sender, channel};
+    use twilight_model::util::Timestamp;
+    use twilight_util::builder::embed::{EmbedBuilder, EmbedFooterBuilder};
+
+    use crate::{
+        poll::announcements::Event,
+        webhook::{Destination, Sink},
+    };
+
+    pub fn background_channel(sink: Sink, program_handle: String) -> Sender<Event> {
+        let (sender, mut receiver) = channel(1);
+        tokio::task::spawn(async move {
+            while let Some(event) = receiver.recv().await {
+                info!("recieved event {event:?}");
+                if let Err(error) = handle_event(&sink.destination(), &program_handle, event).await
+                {
+                    error!("failed to handle event {error:#?}");
+                    break;
+                }
+            }
+        });
+
+        sender
+    }
+
+    const BUGCROWD_THEME_COLOR: u32 = 15886370;
+    const TITLE_LIMIT: usize = 256;
+    const DESCRIPTION_LIMIT: usize = 4096;
+
+    async fn handle_event(
+        destination: &Destination,
+        program_handle: &str,
+        event: Event,
+    ) -> Result<(), anyhow::Error> {
+        let variables = match &event {
+            Event::Announced(announcement) => json!({ "announcement": announcement }),
+        };
+        let embed = match event {
+            Event::Announced(announcement) => {
+                let url =
+                    format!("https://bugcrowd.com/engagements/{program_handle}/announcements");
+                let mut embed = EmbedBuilder::new()
+                    .color(BUGCROWD_THEME_COLOR)
+                    .title(truncate(&announcement.title, TITLE_LIMIT))
+                    .url(url)
+                    .footer(EmbedFooterBuilder::new(format!(
+                        "{program_handle} announcement"
+                    )));
+
+                let body = discord_markdown(&announcement.body);
+                if !body.is_empty() {
+                    embed = embed.description(truncate(&body, DESCRIPTION_LIMIT));
+                }
+                if let Some(published_at) = announcement.published_at {
+                    embed = embed.timestamp(Timestamp::from_secs(published_at.timestamp())?);
+                }
+
+                embed.validate()?.build()
+            }
+        };
+
+        debug!("sending embed: {embed:?}");
+        destination.send(embed, variables).await?;
+        Ok(())
+    }
+
+    /// Rewrites the Markdown Discord can't render in embeds: images become
+    /// links, deep headings become bold, HTML line breaks and rules are dropped.
+    fn discord_markdown(body: &str) -> String {
+        let mut lines = vec![];
+
+        for line in body.replace("\r\n", "\n").lines() {
+            let trimmed = line.trim();
+            if ["---", "***", "___"].contains(&trimmed) {
+                continue;
+            }
+
+            let mut line = line
+                .replace("<br>", "\n")
+                .replace("<br/>", "\n")
+                .replace("<br />", "\n")
+                .replace("![", "[");
+            let heading_level = trimmed.chars().take_while(|c| *c == '#').count();
+            if heading_level > 3 {
+                line = format!("**{}**", trimmed.trim_start_matches('#').trim());
+            }
+
+            lines.push(line);
+        }
+
+        lines.join("\n").trim().to_string()
+    }
+
+    fn truncate(text: &str, limit: usize) -> String {
+        if text.chars().count() <= limit {
+            return text.to_string();
+        }
+
+        let mut truncated: String = text.chars().take(limit - 1).collect();
+        truncated.push('…');
+        truncated
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn markdown() {
+            let body = "#### Scope freeze\r\n---\r\n![diagram](https://x/y.png)<br>Thanks";
+            assert_eq!(
+                discord_markdown(body),
+                "**Scope freeze**\n[diagram](https://x/y.png)\nThanks"
+            );
+            assert_eq!(truncate("abcdef", 4), "abc…");
         }
     }
 }
+
+pub mod brief {
+    use bugcrowd_api::models::{RewardRange, Target};
+    use log::{debug, error, info};
+    use serde_json::json;
+    use tokio::sync::mpsc::{Sender, channel};
+    use twilight_util::builder::embed::{EmbedBuilder, EmbedFooterBuilder};
+
+    use crate::{
+        poll::brief::Event,
+        webhook::{Destination, Sink},
+    };
+
+    pub fn background_channel(sink: Sink, program_handle: String) -> Sender<Event> {
+        let (sender, mut receiver) = channel(1);
+        tokio::task::spawn(async move {
+            while let Some(event) = receiver.recv().await {
+                info!("recieved event {event:?}");
+                if let Err(error) = handle_event(&sink.destination(), &program_handle, event).await
+                {
+                    error!("failed to handle event {error:#?}");
+                    break;
+                }
+            }
+        });
+
+        sender
+    }
+
+    const BUGCROWD_THEME_COLOR: u32 = 15886370;
+    const SUCCESS_COLOR: u32 = 5294200;
+    const FAILURE_COLOR: u32 = 16711680;
+
+    async fn handle_event(
+        destination: &Destination,
+        program_handle: &str,
+        event: Event,
+    ) -> Result<(), anyhow::Error> {
+        let variables = match &event {
+            Event::TargetAdded(target) | Event::TargetRemoved(target) => {
+                json!({ "target": target })
+            }
+            Event::TargetScopeChanged(old, new) => json!({ "target": new, "previous": old }),
+            Event::RewardChanged(old, new) => json!({ "reward": new, "previous": old }),
+            Event::StatusChanged(old, new) => json!({ "status": new, "previous": old }),
+        };
+        let (content, color) = match event {
+            Event::TargetAdded(target) => {
+                let scope = if target.in_scope {
+                    "scope"
+                } else {
+                    "out of scope"
+                };
+                (
+                    format!("target {} added to {scope}", display(&target)),
+                    SUCCESS_COLOR,
+                )
+            }
+
+            Event::TargetRemoved(target) => {
+                let scope = if target.in_scope {
+                    "scope"
+                } else {
+                    "out of scope"
+                };
+                (
+                    format!("target {} removed from {scope}", display(&target)),
+                    FAILURE_COLOR,
+                )
+            }
+
+            Event::TargetScopeChanged(_, target) => {
+                if target.in_scope {
+                    (
+                        format!("target {} moved into scope", display(&target)),
+                        SUCCESS_COLOR,
+                    )
+                } else {
+                    (
+                        format!("target {} moved out of scope", display(&target)),
+                        FAILURE_COLOR,
+                    )
+                }
+            }
+
+            Event::RewardChanged(old, new) => reward_change(old, new),
+
+            Event::StatusChanged(old, new) => (
+                format!("program status changed from **{old}** to **{new}**"),
+                BUGCROWD_THEME_COLOR,
+            ),
+        };
+
+        let embed = EmbedBuilder::new()
+            .color(color)
+            .description(content)
+            .footer(EmbedFooterBuilder::new(program_handle))
+            .validate()?
+            .build();
+
+        debug!("sending embed: {embed:?}");
+        destination.send(embed, variables).await?;
+        Ok(())
+    }
+
+    fn reward_change(old: Option<RewardRange>, new: Option<RewardRange>) -> (String, u32) {
+        match (old, new) {
+            (None, Some(new)) => (
+                format!("{} rewards added: **{}**", new.priority, range(&new)),
+                SUCCESS_COLOR,
+            ),
+            (Some(old), None) => (
+                format!("{} rewards removed (was {})", old.priority, range(&old)),
+                FAILURE_COLOR,
+            ),
+            (Some(old), Some(new)) => {
+                let mut changes = vec![];
+                let mut raised = true;
+                for (bound, old_value, new_value) in
+                    [("min", old.min, new.min), ("max", old.max, new.max)]
+                {
+                    if old_value == new_value {
+                        continue;
+                    }
+
+                    let verb = if new_value.unwrap_or(0) > old_value.unwrap_or(0) {
+                        "raised"
+                    } else {
+                        raised = false;
+                        "lowered"
+                    };
+                    changes.push(format!(
+                        "{} {bound} reward {verb} from {} to **{}**",
+                        new.priority,
+                        amount(old_value),
+                        amount(new_value)
+                    ));
+                }
+
+                let color = if raised { SUCCESS_COLOR } else { FAILURE_COLOR };
+                (changes.join("\n"), color)
+            }
+            (None, None) => (String::new(), BUGCROWD_THEME_COLOR),
+        }
+    }
+
+    fn display(target: &Target) -> String {
+        match &target.category {
+            Some(category) => format!("**`{}`** ({category})", target.name),
+            None => format!("**`{}`**", target.name),
+        }
+    }
+
+    fn range(reward: &RewardRange) -> String {
+        format!("{} - {}", amount(reward.min), amount(reward.max))
+    }
+
+    fn amount(amount: Option<u64>) -> String {
+        match amount {
+            Some(amount) if amount >= 1000 && amount % 1000 == 0 => format!("${}k", amount / 1000),
+            Some(amount) => format!("${amount}"),
+            None => "none".to_string(),
+        }
+    }
+}
+
+pub mod engagements {
+    use log::{debug, error, info};
+    use serde_json::json;
+    use tokio::sync::mpsc::{Sender, channel};
+    use twilight_util::builder::embed::EmbedBuilder;
+
+    use crate::{
+        poll::engagements::Event,
+        webhook::{Destination, Sink},
+    };
+
+    pub fn background_channel(sink: Sink) -> Sender<Event> {
+        let (sender, mut receiver) = channel(1);
+        tokio::task::spawn(async move {
+            while let Some(event) = receiver.recv().await {
+                info!("recieved event {event:?}");
+                if let Err(error) = handle_event(&sink.destination(), event).await {
+                    error!("failed to handle event {error:#?}");
+                    break;
+                }
+            }
+        });
+
+        sender
+    }
+
+    const BUGCROWD_THEME_COLOR: u32 = 15886370;
+    const FAILURE_COLOR: u32 = 16711680;
+
+    async fn handle_event(destination: &Destination, event: Event) -> Result<(), anyhow::Error> {
+        let variables = match &event {
+            Event::Invited(engagement) => json!({ "engagement": engagement }),
+            Event::Disappeared(handle) => json!({ "handle": handle }),
+        };
+        let embed = match event {
+            Event::Invited(engagement) => {
+                let url = format!("https://bugcrowd.com{}", engagement.brief_url);
+                EmbedBuilder::new()
+                    .color(BUGCROWD_THEME_COLOR)
+                    .title(format!("You were invited to {}", engagement.name))
+                    .url(url)
+                    .validate()?
+                    .build()
+            }
+
+            Event::Disappeared(handle) => {
+                let content = format!("**`{handle}`** is no longer visible to the session");
+                EmbedBuilder::new()
+                    .color(FAILURE_COLOR)
+                    .description(content)
+                    .validate()?
+                    .build()
+            }
+        };
+
+        debug!("sending embed: {embed:?}");
+        destination.send(embed, variables).await?;
+        Ok(())
+    }
+}
+
+pub mod researchers {
+    use log::{debug, error, info};
+    use serde_json::json;
+    use tokio::sync::mpsc::{Sender, channel};
+    use twilight_util::builder::embed::{EmbedBuilder, EmbedFooterBuilder};
+
+    use crate::{
+        poll::researchers::Event,
+        webhook::{Destination, Sink},
+    };
+
+    pub fn background_channel(sink: Sink) -> Sender<Event> {
+        let (sender, mut receiver) = channel(1);
+        tokio::task::spawn(async move {
+            while let Some(event) = receiver.recv().await {
+                info!("recieved event {event:?}");
+                if let Err(error) = handle_event(&sink.destination(), event).await {
+                    error!("failed to handle event {error:#?}");
+                    break;
+                }
+            }
+        });
+
+        sender
+    }
+
+    const BUGCROWD_THEME_COLOR: u32 = 15886370;
+    const SUCCESS_COLOR: u32 = 5294200;
+
+    async fn handle_event(destination: &Destination, event: Event) -> Result<(), anyhow::Error> {
+        let variables = match &event {
+            Event::AppearedInHallOfFame(program_handle, hero) => {
+                json!({ "program": program_handle, "hero": hero })
+            }
+            Event::PointsGained(old, new) => json!({ "profile": new, "previous": old }),
+            Event::ReportDisclosed(username, report) => {
+                json!({ "username": username, "report": report })
+            }
+        };
+        let embed = match event {
+            Event::AppearedInHallOfFame(program_handle, hero) => {
+                let content = format!(
+                    "{} appeared in the **{program_handle}** hall of fame\n-# (rank: #{})",
+                    display(&hero.username),
+                    hero.rank
+                );
+                EmbedBuilder::new()
+                    .color(BUGCROWD_THEME_COLOR)
+                    .description(content)
+                    .validate()?
+                    .build()
+            }
+
+            Event::PointsGained(old, new) => {
+                let gained = new.stats.points - old.stats.points;
+                let content = format!(
+                    "{} gained **+{gained} points** and now has **{} points**",
+                    display(&new.username),
+                    new.stats.points
+                );
+                let mut embed = EmbedBuilder::new()
+                    .color(SUCCESS_COLOR)
+                    .description(content);
+
+                match (old.rank, new.rank) {
+                    (Some(old_rank), Some(new_rank)) if old_rank != new_rank => {
+                        let footer = format!("#{old_rank} -> #{new_rank}");
+                        embed = embed.footer(EmbedFooterBuilder::new(footer));
+                    }
+                    _ => {}
+                }
+
+                embed.validate()?.build()
+            }
+
+            Event::ReportDisclosed(username, report) => {
+                let url = format!("https://bugcrowd.com{}", report.disclosure_report_url);
+                let content = format!("{} had a report disclosed", display(&username));
+                EmbedBuilder::new()
+                    .color(report.priority.color())
+                    .title(report.title)
+                    .url(url)
+                    .description(content)
+                    .validate()?
+                    .build()
+            }
+        };
+
+        debug!("sending embed: {embed:?}");
+        destination.send(embed, variables).await?;
+        Ok(())
+    }
+
+    fn display(username: &str) -> String {
+        format!("[**`{username}`**](https://bugcrowd.com/h/{username})")
+    }
+}
+
+pub mod session {
+    use bugcrowd_api::session::SessionState;
+    use log::{debug, error, info};
+    use serde_json::{Value, json};
+    use tokio::sync::mpsc::{Sender, channel};
+    use twilight_util::builder::embed::EmbedBuilder;
+
+    use crate::{
+        poll::session::Event,
+        webhook::{Destination, Sink},
+    };
+
+    pub fn background_channel(sink: Sink) -> Sender<Event> {
+        let (sender, mut receiver) = channel(1);
+        tokio::task::spawn(async move {
+            while let Some(event) = receiver.recv().await {
+                info!("recieved event {event:?}");
+                if let Err(error) = handle_event(&sink.destination(), event).await {
+                    error!("failed to handle event {error:#?}");
+                    break;
+                }
+            }
+        });
+
+        sender
+    }
+
+    const SUCCESS_COLOR: u32 = 5294200;
+    const FAILURE_COLOR: u32 = 16711680;
+
+    async fn handle_event(destination: &Destination, event: Event) -> Result<(), anyhow::Error> {
+        let variables = match &event {
+            Event::Restored(SessionState::Authenticated(user)) => json!({ "user": user }),
+            Event::Expired | Event::Restored(_) => Value::Null,
+        };
+        let embed = match event {
+            Event::Expired => EmbedBuilder::new()
+                .color(FAILURE_COLOR)
+                .title("Bugcrowd session expired")
+                .description(
+                    "The session is no longer authenticated, private programs will look empty until the session token is replaced.",
+                )
+                .validate()?
+                .build(),
+
+            Event::Restored(state) => {
+                let content = match state {
+                    SessionState::Authenticated(user) => match user.username {
+                        Some(username) => format!("Authenticated again as **`{username}`**"),
+                        None => "Authenticated again".to_string(),
+                    },
+                    _ => "Authenticated again".to_string(),
+                };
+                EmbedBuilder::new()
+                    .color(SUCCESS_COLOR)
+                    .title("Bugcrowd session restored")
+                    .description(content)
+                    .validate()?
+                    .build()
+            }
+        };
+
+        debug!("sending embed: {embed:?}");
+        destination.send(embed, variables).await?;
+        Ok(())
+    }
+}